@@ -1,95 +1,399 @@
 use macroquad::prelude::*;
 
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use particle_life::boundary::Boundary;
+use particle_life::camera::Camera;
+use particle_life::force_law::Kernel;
+use particle_life::integrator::Integrator;
+use particle_life::obstacle::{Obstacle, MASK_THRESHOLD};
+use particle_life::particle::Particle;
+use particle_life::preset_library::{save_concrete_preset, LibraryPreset, PresetLibrary};
+use particle_life::shape::Circle;
+use particle_life::universe::{ForceMode, Universe};
+
 use crate::counter::BoundedCounter;
-use crate::preset::{Preset, PRESETS, PRESETS_COUNT};
-use crate::universe::Universe;
 
-struct AppState<'a> {
+struct AppState {
     universe: Universe,
+    presets: PresetLibrary,
     preset_counter: BoundedCounter,
-    preset_keys: Vec<&'a str>,
-    selected_preset: Option<&'a Preset>,
+    // Seed used for every loaded preset, or a random one per load if `None`.
+    rng_seed: Option<u64>,
 }
 
-impl<'a> AppState<'a> {
-    pub fn new(mut universe: Universe, initial_preset: &'a Preset) -> Self {
-        let preset_keys_max_index = PRESETS_COUNT.checked_sub(1).unwrap_or(0);
-        let preset_keys = PRESETS.keys().cloned().collect::<Vec<_>>();
-
-        universe.load_preset(initial_preset);
+impl AppState {
+    pub fn new(
+        universe: Universe,
+        presets: PresetLibrary,
+        initial_preset: &str,
+        rng_seed: Option<u64>,
+    ) -> Self {
+        let preset_keys_max_index = presets.len().saturating_sub(1);
 
-        Self {
+        let mut state = Self {
             universe,
-            preset_keys,
             preset_counter: BoundedCounter {
+                current: presets.position(initial_preset).unwrap_or(0),
                 upper: preset_keys_max_index,
                 continious: true,
                 ..Default::default()
             },
-            selected_preset: Some(initial_preset),
-        }
+            presets,
+            rng_seed,
+        };
+
+        state.load_current_preset();
+        state
     }
 
-    pub fn current_preset(&self) -> Option<(&str, &Preset)> {
-        if let Some(preset) = self.selected_preset {
-            Some((self.preset_keys[self.preset_counter.current()], preset))
-        } else {
-            None
-        }
+    pub fn current_preset(&self) -> Option<(&str, &LibraryPreset)> {
+        self.presets.get_index(self.preset_counter.current())
     }
 
     pub fn load_next_preset(&mut self) {
-        let index = self.preset_counter.increment();
-        self.selected_preset = PRESETS.get(self.preset_keys[index]);
+        self.preset_counter.increment();
         self.load_current_preset();
     }
 
     pub fn load_prev_preset(&mut self) {
-        let index = self.preset_counter.decrement();
-        self.selected_preset = PRESETS.get(self.preset_keys[index]);
+        self.preset_counter.decrement();
         self.load_current_preset();
     }
 
     pub fn load_current_preset(&mut self) {
-        if let Some(preset) = self.selected_preset {
-            self.universe.load_preset(preset);
+        if let Some((_, preset)) = self.presets.get_index(self.preset_counter.current()) {
+            let rng_seed = self.rng_seed.unwrap_or_else(::rand::random);
+            self.universe.set_rng_seed(rng_seed);
+            preset.load_into(&mut self.universe);
         }
     }
+
+    pub fn add_preset(&mut self, name: String, preset: LibraryPreset) {
+        self.presets.insert(name, preset);
+        self.preset_counter.upper = self.presets.len().saturating_sub(1);
+    }
+}
+
+pub const PHYSICS_SIMULATION_FPS: u32 = 100;
+pub const PHYSICS_DELTA_TIME: f64 = 1.0 / PHYSICS_SIMULATION_FPS as f64;
+
+// Simulation speeds, relative to one step per physics tick
+const SPEEDS: [f64; 9] = [0.0625, 0.125, 0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+const DEFAULT_SPEED_INDEX: usize = 4;
+// Steps of the universe per second of real time at 1x speed
+const STEPS_PER_SECOND: f64 = 60.0;
+// Speed multiplier while Space is held
+const SPEED_BOOST: f64 = 10.0;
+
+const SNAPSHOT_PATH: &str = "snapshot.bin";
+const SNAPSHOT_JSON_PATH: &str = "snapshot.json";
+
+const ZOOM_FACTOR: f32 = 1.1;
+
+// Toggle the force fields of the preset in order
+const FIELD_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+// Size of the mouse brush in world units, and its acceleration at the center
+const DEFAULT_BRUSH_RADIUS: f32 = 80.0;
+const DEFAULT_BRUSH_STRENGTH: f32 = 0.2;
+// Change of the brush per step of the mouse wheel
+const BRUSH_FACTOR: f32 = 1.1;
+const MIN_BRUSH_RADIUS: f32 = 5.0;
+
+// Lowest temperature set with `]`, below it `[` turns the noise off
+const MIN_TEMPERATURE: f32 = 0.001;
+
+const OBSTACLE_COLOR: Color = GRAY;
+const OBSTACLE_THICKNESS: f32 = 2.0;
+// Shorter drags are taken as clicks and don't add an obstacle
+const MIN_OBSTACLE_SIZE: f32 = 2.0;
+
+// Size of the population graph in the bottom left corner
+const GRAPH_WIDTH: f32 = 300.0;
+const GRAPH_HEIGHT: f32 = 100.0;
+
+// What dragging with the left mouse button does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tool {
+    // Attracts particles, or repels them with the right button
+    Force,
+    Spawn,
+    Erase,
+    Paint,
+    // Draw obstacles, see `App::handle_editor_input`
+    Segment,
+    Circle,
+    Polygon,
 }
 
-const STEPS_PER_FRAME_LOW: usize = 1;
-const STEPS_PER_FRAME_HIGH: usize = 10;
+impl Tool {
+    fn name(&self) -> &'static str {
+        match self {
+            Tool::Force => "Force",
+            Tool::Spawn => "Spawn",
+            Tool::Erase => "Erase",
+            Tool::Paint => "Paint",
+            Tool::Segment => "Segment obstacle",
+            Tool::Circle => "Circle obstacle",
+            Tool::Polygon => "Polygon obstacle",
+        }
+    }
+
+    fn edits_obstacles(&self) -> bool {
+        matches!(self, Tool::Segment | Tool::Circle | Tool::Polygon)
+    }
+
+    fn next(&self) -> Self {
+        match self {
+            Tool::Force => Tool::Spawn,
+            Tool::Spawn => Tool::Erase,
+            Tool::Erase => Tool::Paint,
+            Tool::Paint => Tool::Segment,
+            Tool::Segment => Tool::Circle,
+            Tool::Circle => Tool::Polygon,
+            Tool::Polygon => Tool::Force,
+        }
+    }
+}
 
-pub struct App<'a> {
-    state: AppState<'a>,
-    steps_per_frame: usize,
+pub struct App {
+    state: AppState,
+    camera: Camera,
+    // Last mouse position while panning
+    drag_origin: Option<(f32, f32)>,
+    presets_dir: PathBuf,
+    brush_radius: f32,
+    brush_strength: f32,
+    tool: Tool,
+    // Type of the particles spawned or painted
+    selected_type: usize,
+    // World position where the current segment or circle started
+    obstacle_start: Option<(f32, f32)>,
+    // World positions of the corners of the polygon being drawn
+    polygon_points: Vec<(f32, f32)>,
+    // Solid pixels of every mask, by path
+    mask_textures: HashMap<PathBuf, Texture2D>,
+    speed_counter: BoundedCounter,
+    boost: bool,
+    paused: bool,
+    last_snapshot: &'static str,
+    status: Option<String>,
 }
 
-impl<'a> App<'a> {
-    pub fn new(width: f32, height: f32) -> Self {
-        let initial_preset = PRESETS.get("Chaos").unwrap();
+impl App {
+    pub fn new(width: f32, height: f32, presets_dir: &str, rng_seed: Option<u64>) -> Self {
+        let mut presets = PresetLibrary::builtin();
+        let errors = presets.load_dir(presets_dir);
+
+        for error in &errors {
+            eprintln!("{}", error);
+        }
+
+        let status = match errors.len() {
+            0 => None,
+            1 => Some(format!("Skipped preset {}", errors[0])),
+            n => Some(format!("Skipped {} invalid presets, see stderr", n)),
+        };
 
         Self {
-            state: AppState::new(Universe::new(width as f32, height as f32), initial_preset),
-            steps_per_frame: STEPS_PER_FRAME_LOW,
+            state: AppState::new(Universe::new(width, height), presets, "Chaos", rng_seed),
+            camera: Camera::new(width, height),
+            drag_origin: None,
+            presets_dir: PathBuf::from(presets_dir),
+            brush_radius: DEFAULT_BRUSH_RADIUS,
+            brush_strength: DEFAULT_BRUSH_STRENGTH,
+            tool: Tool::Force,
+            selected_type: 0,
+            obstacle_start: None,
+            polygon_points: Vec::new(),
+            mask_textures: HashMap::new(),
+            speed_counter: BoundedCounter {
+                current: DEFAULT_SPEED_INDEX,
+                upper: SPEEDS.len() - 1,
+                ..Default::default()
+            },
+            boost: false,
+            paused: false,
+            last_snapshot: SNAPSHOT_PATH,
+            status,
+        }
+    }
+
+    // Number of physics ticks to simulate per tick of real time.
+    pub fn speed(&self) -> f64 {
+        if self.paused {
+            0.0
+        } else if self.boost {
+            SPEEDS[self.speed_counter.current()] * SPEED_BOOST
+        } else {
+            SPEEDS[self.speed_counter.current()]
         }
     }
 
-    pub fn update(&mut self, _t: f64, _delta: f64) {
-        self.handle_input();
+    pub fn paused(&self) -> bool {
+        self.paused
     }
 
-    pub fn draw(&mut self, _state: f64, _alpha: f64) {
+    pub fn update(&mut self, _t: f64, delta: f64) {
+        self.state.universe.step(delta * STEPS_PER_SECOND);
+    }
+
+    pub fn draw(&mut self, alpha: f64) {
         clear_background(BLACK);
 
-        for i in 0..self.steps_per_frame {
-            let opacity = (i + 1) as f32 / self.steps_per_frame as f32;
-            self.state.universe.step();
-            self.state.universe.draw(opacity);
+        let universe = &self.state.universe;
+        self.camera.set_viewport(screen_width(), screen_height());
+        self.camera
+            .set_world(universe.width(), universe.height(), universe.boundary());
+        if universe.boundary() == Boundary::Open {
+            let (x, y) = universe.center_of_mass();
+            self.camera.center_x = x as f32;
+            self.camera.center_y = y as f32;
         }
+        self.handle_camera_input();
+
+        self.draw_arena();
+        self.draw_obstacles();
+        self.state.universe.draw(&self.camera, alpha);
+        self.draw_brush();
 
         self.draw_fps_counter();
         self.draw_preset_status();
+        self.draw_speed();
+        self.draw_status();
+        self.draw_tool();
+        self.draw_reactions();
+        self.draw_populations();
+    }
+
+    fn draw_arena(&self) {
+        let universe = &self.state.universe;
+        if universe.boundary() != Boundary::Circle {
+            return;
+        }
+
+        let arena = Boundary::arena(universe.width() as f64, universe.height() as f64);
+        let (x, y) = self.camera.to_screen(arena.x as f32, arena.y as f32);
+        let radius = arena.radius as f32 * self.camera.zoom;
+        draw_circle_lines(x, y, radius, 1.0, DARKGRAY);
+    }
+
+    fn draw_obstacles(&mut self) {
+        let universe = &self.state.universe;
+        let camera = &self.camera;
+        let width = universe.width();
+        let height = universe.height();
+        let screen = |x: f32, y: f32| camera.to_screen(x * width, y * height);
+
+        for obstacle in universe.obstacles() {
+            match obstacle {
+                Obstacle::Circle { x, y, radius } => {
+                    let (x, y) = screen(*x, *y);
+                    draw_circle(x, y, radius * camera.zoom, OBSTACLE_COLOR);
+                }
+                Obstacle::Segment { x1, y1, x2, y2 } => {
+                    let (x1, y1) = screen(*x1, *y1);
+                    let (x2, y2) = screen(*x2, *y2);
+                    draw_line(x1, y1, x2, y2, OBSTACLE_THICKNESS, OBSTACLE_COLOR);
+                }
+                Obstacle::Polygon { points } => {
+                    for (i, &[x1, y1]) in points.iter().enumerate() {
+                        let [x2, y2] = points[(i + 1) % points.len()];
+                        let (x1, y1) = screen(x1, y1);
+                        let (x2, y2) = screen(x2, y2);
+                        draw_line(x1, y1, x2, y2, OBSTACLE_THICKNESS, OBSTACLE_COLOR);
+                    }
+                }
+                Obstacle::Mask {
+                    path,
+                    image: Some(image),
+                } => {
+                    let texture = *self.mask_textures.entry(path.clone()).or_insert_with(|| {
+                        let bytes: Vec<u8> = image
+                            .pixels()
+                            .iter()
+                            .flat_map(|&brightness| {
+                                if brightness < MASK_THRESHOLD {
+                                    let color: [u8; 4] = OBSTACLE_COLOR.into();
+                                    color
+                                } else {
+                                    [0; 4]
+                                }
+                            })
+                            .collect();
+                        let texture = Texture2D::from_rgba8(
+                            image.width() as u16,
+                            image.height() as u16,
+                            &bytes,
+                        );
+                        texture.set_filter(FilterMode::Nearest);
+                        texture
+                    });
+
+                    let (x, y) = screen(0.0, 0.0);
+                    draw_texture_ex(
+                        texture,
+                        x,
+                        y,
+                        WHITE,
+                        DrawTextureParams {
+                            dest_size: Some(vec2(width * camera.zoom, height * camera.zoom)),
+                            ..Default::default()
+                        },
+                    );
+                }
+                Obstacle::Mask { image: None, .. } => {}
+            }
+        }
+    }
+
+    // Outline of the obstacle being drawn
+    fn draw_obstacle_preview(&self) {
+        let (x, y) = mouse_position();
+        let corners = self.polygon_points.iter().chain(self.obstacle_start.iter());
+
+        let mut last = None;
+        for &(px, py) in corners {
+            let (px, py) = self.camera.to_screen(px, py);
+            if let Some((lx, ly)) = last {
+                draw_line(lx, ly, px, py, 1.0, WHITE);
+            }
+            last = Some((px, py));
+        }
+
+        if let Some((lx, ly)) = last {
+            if self.tool == Tool::Circle {
+                draw_circle_lines(lx, ly, (x - lx).hypot(y - ly), 1.0, WHITE);
+            } else {
+                draw_line(lx, ly, x, y, 1.0, WHITE);
+            }
+        }
+    }
+
+    fn draw_brush(&self) {
+        if self.tool.edits_obstacles() {
+            self.draw_obstacle_preview();
+            return;
+        }
+
+        let (x, y) = mouse_position();
+        let active =
+            is_mouse_button_down(MouseButton::Left) || is_mouse_button_down(MouseButton::Right);
+        let color = if active { WHITE } else { DARKGRAY };
+        draw_circle_lines(x, y, self.brush_radius * self.camera.zoom, 1.0, color);
     }
 
     fn draw_fps_counter(&self) {
@@ -100,9 +404,314 @@ impl<'a> App<'a> {
         let preset = self.state.current_preset();
         let title = preset.map(|x| x.0).unwrap_or("");
         draw_text(&format!("< {} >", title), 20.0, 40.0, 20.0, WHITE);
+        draw_text(
+            &format!("Seed: {}", self.state.universe.rng_seed()),
+            20.0,
+            60.0,
+            20.0,
+            DARKGRAY,
+        );
+    }
+
+    fn draw_speed(&self) {
+        let speed = if self.paused {
+            "Paused".to_owned()
+        } else {
+            format!("Speed: {}x", self.speed())
+        };
+        draw_text(&speed, 20.0, 80.0, 20.0, DARKGRAY);
+    }
+
+    fn draw_tool(&self) {
+        let universe = &self.state.universe;
+        let (text, color) = match self.tool {
+            Tool::Spawn | Tool::Paint => {
+                let particle_type = self.selected_type();
+                let color = *universe.types().get_color(particle_type).unwrap();
+                (
+                    format!("Tool: {} type {}", self.tool.name(), particle_type + 1),
+                    color.into(),
+                )
+            }
+            _ => (format!("Tool: {}", self.tool.name()), DARKGRAY),
+        };
+        draw_text(&text, 20.0, 120.0, 20.0, color);
+    }
+
+    // How often each reaction happened, type numbers starting at 1 like the
+    // tools.
+    fn draw_reactions(&self) {
+        let universe = &self.state.universe;
+        let reactions = universe.types().reactions();
+
+        for (i, (reaction, count)) in reactions.iter().zip(universe.reaction_counts()).enumerate() {
+            draw_text(
+                &format!(
+                    "{} + {} -> {}: {}",
+                    reaction.reactant + 1,
+                    reaction.catalyst + 1,
+                    reaction.product + 1,
+                    count
+                ),
+                20.0,
+                140.0 + 20.0 * i as f32,
+                20.0,
+                DARKGRAY,
+            );
+        }
+    }
+
+    // Population of every type over the recorded steps of the ecology.
+    fn draw_populations(&self) {
+        let universe = &self.state.universe;
+        let history = universe.population_history();
+        if universe.ecology().is_none() || history.len() < 2 {
+            return;
+        }
+
+        let left = 20.0;
+        let top = screen_height() - 20.0 - GRAPH_HEIGHT;
+        draw_rectangle_lines(left, top, GRAPH_WIDTH, GRAPH_HEIGHT, 1.0, DARKGRAY);
+
+        let largest = history.iter().flatten().copied().max().unwrap_or(0).max(1);
+        // At most one point per pixel
+        let points = history.len().min(GRAPH_WIDTH as usize);
+        let sample = |i: usize| &history[i * (history.len() - 1) / (points - 1)];
+
+        for t in 0..universe.types().size() {
+            let color: Color = (*universe.types().get_color(t).unwrap()).into();
+            let position = |i: usize| {
+                let count = sample(i).get(t).copied().unwrap_or(0);
+                (
+                    left + GRAPH_WIDTH * i as f32 / (points - 1) as f32,
+                    top + GRAPH_HEIGHT * (1.0 - count as f32 / largest as f32),
+                )
+            };
+
+            for i in 1..points {
+                let (x1, y1) = position(i - 1);
+                let (x2, y2) = position(i);
+                draw_line(x1, y1, x2, y2, 1.0, color);
+            }
+        }
+
+        draw_text(
+            &format!("Population: {}", universe.particles().len()),
+            left,
+            top - 5.0,
+            20.0,
+            DARKGRAY,
+        );
+    }
+
+    fn draw_status(&self) {
+        if let Some(status) = &self.status {
+            draw_text(status, 20.0, 100.0, 20.0, DARKGRAY);
+        }
+    }
+
+    fn save_snapshot(&mut self, path: &'static str) {
+        self.status = Some(match self.state.universe.save_snapshot(path) {
+            Ok(()) => {
+                self.last_snapshot = path;
+                format!("Saved {}", path)
+            }
+            Err(e) => format!("Failed to save {}: {}", path, e),
+        });
+    }
+
+    fn load_snapshot(&mut self) {
+        let path = self.last_snapshot;
+        self.status = Some(match self.state.universe.load_snapshot(path) {
+            Ok(()) => format!("Loaded {}", path),
+            Err(e) => format!("Failed to load {}: {}", path, e),
+        });
+    }
+
+    // Saves the interaction matrices of the current world as a concrete preset
+    // and adds it to the presets that can be cycled through.
+    fn export_preset(&mut self) {
+        let title = self.state.current_preset().map(|x| x.0).unwrap_or("World");
+        let name = format!("{} {}", title, self.state.universe.rng_seed());
+        let path = self.presets_dir.join(format!("{}.json", name));
+        let preset = self.state.universe.concrete_preset();
+
+        let result = fs::create_dir_all(&self.presets_dir)
+            .map_err(Into::into)
+            .and_then(|_| save_concrete_preset(&path, &preset));
+
+        self.status = Some(match result {
+            Ok(()) => {
+                self.state.add_preset(name, LibraryPreset::Concrete(preset));
+                format!("Exported {}", path.display())
+            }
+            Err(e) => format!("Failed to export {}: {}", path.display(), e),
+        });
+    }
+
+    fn handle_camera_input(&mut self) {
+        let (x, y) = mouse_position();
+
+        let (_, wheel) = mouse_wheel();
+        if wheel != 0.0 {
+            let factor = BRUSH_FACTOR.powf(wheel.signum());
+
+            if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) {
+                self.brush_radius = (self.brush_radius * factor).max(MIN_BRUSH_RADIUS);
+                self.status = Some(format!("Brush radius: {:.0}", self.brush_radius));
+            } else if is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl) {
+                self.brush_strength *= factor;
+                self.status = Some(format!("Brush strength: {:.3}", self.brush_strength));
+            } else {
+                self.camera.zoom_at(x, y, ZOOM_FACTOR.powf(wheel.signum()));
+            }
+        }
+
+        if is_mouse_button_down(MouseButton::Middle) {
+            if let Some((last_x, last_y)) = self.drag_origin {
+                self.camera.pan(x - last_x, y - last_y);
+            }
+            self.drag_origin = Some((x, y));
+        } else {
+            self.drag_origin = None;
+        }
+
+        if is_key_pressed(KeyCode::Home) {
+            self.camera.reset();
+        }
+    }
+
+    // The selected type may not exist after switching presets.
+    fn selected_type(&self) -> usize {
+        self.selected_type % self.state.universe.types().size().max(1)
+    }
+
+    // Applies the current tool under the cursor while a mouse button is held.
+    fn handle_brush_input(&mut self) {
+        self.state.universe.clear_brush();
+
+        let left = is_mouse_button_down(MouseButton::Left);
+        let right = is_mouse_button_down(MouseButton::Right);
+        if !left && !right {
+            return;
+        }
+
+        let (x, y) = mouse_position();
+        let (x, y) = self.camera.to_world(x, y);
+        let circle = Circle::new(x as f64, y as f64, self.brush_radius as f64);
+        let particle_type = self.selected_type();
+        let universe = &mut self.state.universe;
+
+        match self.tool {
+            Tool::Force => {
+                let strength = if left {
+                    self.brush_strength
+                } else {
+                    -self.brush_strength
+                };
+                universe.set_brush(x, y, self.brush_radius, strength);
+            }
+            Tool::Spawn if left => {
+                // Uniformly distributed within the brush
+                let angle = macroquad::rand::gen_range(0.0, std::f64::consts::TAU);
+                let distance = macroquad::rand::gen_range(0.0f64, 1.0).sqrt() * circle.radius;
+                universe.add_particle(Particle {
+                    x: circle.x + distance * angle.cos(),
+                    y: circle.y + distance * angle.sin(),
+                    particle_type,
+                    energy: universe.initial_energy(),
+                    ..Default::default()
+                });
+            }
+            Tool::Erase if left => {
+                universe.remove_particles_in(circle);
+            }
+            Tool::Paint if left => {
+                universe.set_type_in(circle, particle_type);
+            }
+            _ => {}
+        }
+    }
+
+    // Segments and circles are drawn by dragging with the left mouse button,
+    // polygons by clicking their corners and closing them with the right one.
+    fn handle_editor_input(&mut self) {
+        if is_key_pressed(KeyCode::Backspace) {
+            let universe = &mut self.state.universe;
+            self.status = Some(match universe.obstacles().len() {
+                0 => "No obstacles to remove".to_owned(),
+                n => format!("Removed {}", universe.remove_obstacle(n - 1).name()),
+            });
+        }
+
+        let (x, y) = mouse_position();
+        let (x, y) = self.camera.to_world(x, y);
+        let universe = &mut self.state.universe;
+        let width = universe.width();
+        let height = universe.height();
+
+        match self.tool {
+            Tool::Segment | Tool::Circle => {
+                if is_mouse_button_pressed(MouseButton::Left) {
+                    self.obstacle_start = Some((x, y));
+                }
+
+                if !is_mouse_button_released(MouseButton::Left) {
+                    return;
+                }
+
+                let (x0, y0) = match self.obstacle_start.take() {
+                    Some(start) => start,
+                    None => return,
+                };
+
+                let length = (x - x0).hypot(y - y0);
+                if length < MIN_OBSTACLE_SIZE {
+                    return;
+                }
+
+                universe.add_obstacle(if self.tool == Tool::Segment {
+                    Obstacle::Segment {
+                        x1: x0 / width,
+                        y1: y0 / height,
+                        x2: x / width,
+                        y2: y / height,
+                    }
+                } else {
+                    Obstacle::Circle {
+                        x: x0 / width,
+                        y: y0 / height,
+                        radius: length,
+                    }
+                });
+            }
+            Tool::Polygon => {
+                if is_mouse_button_pressed(MouseButton::Left) {
+                    self.polygon_points.push((x, y));
+                }
+
+                if is_mouse_button_pressed(MouseButton::Right) {
+                    if self.polygon_points.len() >= 3 {
+                        universe.add_obstacle(Obstacle::Polygon {
+                            points: self
+                                .polygon_points
+                                .iter()
+                                .map(|&(x, y)| [x / width, y / height])
+                                .collect(),
+                        });
+                    }
+                    self.polygon_points.clear();
+                }
+            }
+            _ => {}
+        }
     }
 
-    fn handle_input(&mut self) {
+    // Called once per frame, independently of the physics ticks.
+    pub fn handle_input(&mut self) {
+        self.handle_brush_input();
+        self.handle_editor_input();
+
         if is_key_pressed(KeyCode::Right) {
             self.state.load_next_preset();
         }
@@ -111,12 +720,124 @@ impl<'a> App<'a> {
             self.state.load_prev_preset();
         }
 
-        if is_key_down(KeyCode::Space) {
-            self.steps_per_frame = STEPS_PER_FRAME_HIGH;
+        self.boost = is_key_down(KeyCode::Space);
+
+        if is_key_pressed(KeyCode::Tab) {
+            self.tool = self.tool.next();
+            self.obstacle_start = None;
+            self.polygon_points.clear();
+        }
+
+        let types = self.state.universe.types().size().max(1);
+        if is_key_pressed(KeyCode::Up) {
+            self.selected_type = (self.selected_type() + 1) % types;
+        }
+
+        if is_key_pressed(KeyCode::Down) {
+            self.selected_type = (self.selected_type() + types - 1) % types;
+        }
+
+        if is_key_pressed(KeyCode::P) {
+            self.paused = !self.paused;
+        }
+
+        // Single tick while paused
+        if is_key_pressed(KeyCode::Period) && self.paused {
+            self.update(0.0, PHYSICS_DELTA_TIME);
+        }
+
+        if is_key_pressed(KeyCode::Equal) || is_key_pressed(KeyCode::KpAdd) {
+            self.speed_counter.increment();
+        }
+
+        if is_key_pressed(KeyCode::Minus) || is_key_pressed(KeyCode::KpSubtract) {
+            self.speed_counter.decrement();
         }
 
-        if is_key_released(KeyCode::Space) {
-            self.steps_per_frame = STEPS_PER_FRAME_LOW;
+        if is_key_pressed(KeyCode::F5) {
+            if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) {
+                self.save_snapshot(SNAPSHOT_JSON_PATH);
+            } else {
+                self.save_snapshot(SNAPSHOT_PATH);
+            }
+        }
+
+        if is_key_pressed(KeyCode::F9) {
+            self.load_snapshot();
+        }
+
+        if is_key_pressed(KeyCode::E) {
+            self.export_preset();
+        }
+
+        if is_key_pressed(KeyCode::I) {
+            let integrator = match self.state.universe.integrator() {
+                Integrator::SymplecticEuler => Integrator::VelocityVerlet,
+                Integrator::VelocityVerlet => Integrator::Rk4,
+                Integrator::Rk4 => Integrator::SymplecticEuler,
+            };
+            self.state.universe.set_integrator(integrator);
+            self.status = Some(format!("Integrator: {}", integrator.name()));
+        }
+
+        if is_key_pressed(KeyCode::K) {
+            let current = self.state.universe.kernel();
+            let index = Kernel::ALL.iter().position(|&k| k == current).unwrap();
+            let kernel = Kernel::ALL[(index + 1) % Kernel::ALL.len()];
+            self.state.universe.set_kernel(kernel);
+            self.status = Some(format!("Kernel for all types: {}", kernel.name()));
+        }
+
+        if is_key_pressed(KeyCode::RightBracket) {
+            let temperature = match self.state.universe.temperature() {
+                t if t > 0.0 => t * 2.0,
+                _ => MIN_TEMPERATURE,
+            };
+            self.state.universe.set_temperature(temperature);
+            self.status = Some(format!("Temperature: {}", temperature));
+        }
+
+        if is_key_pressed(KeyCode::LeftBracket) {
+            let temperature = match self.state.universe.temperature() * 0.5 {
+                t if t >= MIN_TEMPERATURE => t,
+                _ => 0.0,
+            };
+            self.state.universe.set_temperature(temperature);
+            self.status = Some(format!("Temperature: {}", temperature));
+        }
+
+        if is_key_pressed(KeyCode::B) {
+            let current = self.state.universe.boundary();
+            let index = Boundary::ALL.iter().position(|&b| b == current).unwrap();
+            let boundary = Boundary::ALL[(index + 1) % Boundary::ALL.len()];
+            self.state.universe.set_boundary(boundary);
+            self.status = Some(format!("Boundary: {}", boundary.name()));
+        }
+
+        if is_key_pressed(KeyCode::R) {
+            let (mode, name) = match self.state.universe.force_mode() {
+                ForceMode::Asymmetric => (ForceMode::Reciprocal, "reciprocal"),
+                ForceMode::Reciprocal => (ForceMode::Asymmetric, "asymmetric"),
+            };
+            self.state.universe.set_force_mode(mode);
+            self.status = Some(format!("Forces: {}", name));
+        }
+
+        for (index, &key) in FIELD_KEYS.iter().enumerate() {
+            if !is_key_pressed(key) {
+                continue;
+            }
+
+            let universe = &mut self.state.universe;
+            self.status = Some(match universe.toggle_force_field(index) {
+                Some(enabled) => format!(
+                    "Field {} ({}): {}",
+                    index + 1,
+                    universe.force_fields()[index].name(),
+                    if enabled { "on" } else { "off" }
+                ),
+                None => format!("No field {}", index + 1),
+            });
         }
     }
 }
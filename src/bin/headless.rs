@@ -0,0 +1,277 @@
+// Runs a preset without opening a window and dumps the particle state as
+// newline-delimited JSON, one line per dumped step.
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+use particle_life::boundary::Boundary;
+use particle_life::integrator::Integrator;
+use particle_life::preset_library::PresetLibrary;
+use particle_life::thermostat::Thermostat;
+use particle_life::universe::{Backend, ForceMode, Universe};
+use serde::Serialize;
+
+const USAGE: &str = "\
+Usage: particle-life-headless --preset <NAME> --steps <N> [OPTIONS]
+
+Options:
+    --preset <NAME>      Preset to simulate
+    --presets <DIR>      Directory with additional presets [default: presets]
+    --steps <N>          Number of steps to simulate
+    --out <PATH>         Output file (defaults to stdout)
+    --interval <N>       Dump particle state every N steps [default: 100]
+    --width <W>          Width of the universe [default: 800]
+    --height <H>         Height of the universe [default: 600]
+    --seed <SEED>        Seed for the random engine [default: random]
+    --integrator <NAME>  `euler`, `verlet` or `rk4` [default: euler]
+    --backend <BACKEND>  Force backend, `serial` or `parallel` [default: parallel]
+    --reciprocal         Apply equal and opposite forces to every pair
+    --temperature <T>    Random motion of all types [default: from the preset]
+    --thermostat <KE>    Hold the mean kinetic energy per particle at KE
+    --boundary <NAME>    `wrap`, `reflect`, `absorb`, `soft`, `circle` or `open`
+                         [default: from the preset]
+    --list               List the available presets, including those in --presets
+    --help               Print this message";
+
+struct Args {
+    preset: String,
+    presets: PresetLibrary,
+    steps: usize,
+    out: Option<String>,
+    interval: usize,
+    width: f32,
+    height: f32,
+    seed: Option<u64>,
+    integrator: Integrator,
+    backend: Backend,
+    force_mode: ForceMode,
+    temperature: Option<f32>,
+    thermostat: Option<Thermostat>,
+    boundary: Option<Boundary>,
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("missing value for {}", flag))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", flag, value))
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut preset = None;
+    let mut presets_dir = "presets".to_owned();
+    let mut list = false;
+    let mut steps = None;
+    let mut out = None;
+    let mut interval = 100;
+    let mut width = 800.0;
+    let mut height = 600.0;
+    let mut seed = None;
+    let mut integrator = Integrator::default();
+    let mut backend = Backend::Parallel;
+    let mut force_mode = ForceMode::Asymmetric;
+    let mut temperature = None;
+    let mut thermostat = None;
+    let mut boundary = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--preset" => preset = Some(parse_value::<String>(&arg, args.next())?),
+            "--presets" => presets_dir = parse_value(&arg, args.next())?,
+            "--steps" => steps = Some(parse_value(&arg, args.next())?),
+            "--out" => out = Some(parse_value(&arg, args.next())?),
+            "--interval" => interval = parse_value(&arg, args.next())?,
+            "--width" => width = parse_value(&arg, args.next())?,
+            "--height" => height = parse_value(&arg, args.next())?,
+            "--seed" => seed = Some(parse_value(&arg, args.next())?),
+            "--integrator" => {
+                integrator = match parse_value::<String>(&arg, args.next())?.as_str() {
+                    "euler" => Integrator::SymplecticEuler,
+                    "verlet" => Integrator::VelocityVerlet,
+                    "rk4" => Integrator::Rk4,
+                    other => return Err(format!("unknown integrator: {}", other)),
+                }
+            }
+            "--backend" => {
+                backend = match parse_value::<String>(&arg, args.next())?.as_str() {
+                    "serial" => Backend::Serial,
+                    "parallel" => Backend::Parallel,
+                    other => return Err(format!("unknown backend: {}", other)),
+                }
+            }
+            "--reciprocal" => force_mode = ForceMode::Reciprocal,
+            "--temperature" => temperature = Some(parse_value(&arg, args.next())?),
+            "--thermostat" => {
+                thermostat = Some(Thermostat::Rescale {
+                    kinetic_energy: parse_value(&arg, args.next())?,
+                })
+            }
+            "--boundary" => {
+                boundary = Some(match parse_value::<String>(&arg, args.next())?.as_str() {
+                    "wrap" => Boundary::Wrap,
+                    "reflect" => Boundary::Reflect,
+                    "absorb" => Boundary::Absorb,
+                    "soft" => Boundary::Soft,
+                    "circle" => Boundary::Circle,
+                    "open" => Boundary::Open,
+                    other => return Err(format!("unknown boundary: {}", other)),
+                })
+            }
+            "--list" => list = true,
+            "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            other => return Err(format!("unknown argument: {}", other)),
+        }
+    }
+
+    let mut presets = PresetLibrary::builtin();
+    for error in presets.load_dir(&presets_dir) {
+        eprintln!("warning: skipped preset {}", error);
+    }
+
+    if list {
+        for name in presets.names() {
+            println!("{}", name);
+        }
+        process::exit(0);
+    }
+
+    if interval == 0 {
+        return Err("--interval must be greater than 0".to_owned());
+    }
+
+    Ok(Args {
+        preset: preset.ok_or("missing --preset")?,
+        presets,
+        steps: steps.ok_or("missing --steps")?,
+        out,
+        interval,
+        width,
+        height,
+        seed,
+        integrator,
+        backend,
+        force_mode,
+        temperature,
+        thermostat,
+        boundary,
+    })
+}
+
+// One line of output, with the optional keys only present while the feature
+// is in use.
+#[derive(Serialize)]
+struct State<'a> {
+    step: usize,
+    kinetic_energy: f64,
+    momentum: (f64, f64),
+    #[serde(skip_serializing_if = "<[u64]>::is_empty")]
+    reactions: &'a [u64],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    population: Option<Vec<usize>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    bonds: Vec<[usize; 2]>,
+    particles: Vec<ParticleState>,
+}
+
+#[derive(Serialize)]
+struct ParticleState {
+    #[serde(rename = "type")]
+    particle_type: usize,
+    x: f64,
+    y: f64,
+    vx: f64,
+    vy: f64,
+}
+
+// Non-finite numbers are written as `null`, so every line stays valid JSON.
+fn write_state<W: Write>(out: &mut W, step: usize, universe: &Universe) -> io::Result<()> {
+    let state = State {
+        step,
+        kinetic_energy: universe.kinetic_energy(),
+        momentum: universe.momentum(),
+        reactions: universe.reaction_counts(),
+        population: universe.ecology().map(|_| universe.population()),
+        bonds: universe
+            .bonds()
+            .iter()
+            .map(|bond| [bond.a, bond.b])
+            .collect(),
+        particles: universe
+            .particles()
+            .iter()
+            .map(|p| ParticleState {
+                particle_type: p.particle_type,
+                x: p.x,
+                y: p.y,
+                vx: p.vx,
+                vy: p.vy,
+            })
+            .collect(),
+    };
+
+    serde_json::to_writer(&mut *out, &state)?;
+    writeln!(out)
+}
+
+fn run(args: Args) -> Result<(), String> {
+    let preset = args
+        .presets
+        .get(&args.preset)
+        .ok_or_else(|| format!("unknown preset: {}", args.preset))?;
+
+    let mut universe = Universe::new(args.width, args.height);
+    universe.set_backend(args.backend);
+    universe.set_integrator(args.integrator);
+    universe.set_force_mode(args.force_mode);
+    if let Some(seed) = args.seed {
+        universe.set_rng_seed(seed);
+    }
+    preset.load_into(&mut universe);
+    if let Some(temperature) = args.temperature {
+        universe.set_temperature(temperature);
+    }
+    if let Some(thermostat) = args.thermostat {
+        universe.set_thermostat(thermostat);
+    }
+    if let Some(boundary) = args.boundary {
+        universe.set_boundary(boundary);
+    }
+
+    eprintln!("seed: {}", universe.rng_seed());
+
+    let mut out: Box<dyn Write> = match &args.out {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).map_err(|e| format!("{}: {}", path, e))?,
+        )),
+        None => Box::new(BufWriter::new(io::stdout())),
+    };
+
+    let write_error = |e: io::Error| format!("failed to write output: {}", e);
+
+    write_state(&mut out, 0, &universe).map_err(write_error)?;
+
+    for step in 1..=args.steps {
+        universe.step(1.0);
+
+        if step % args.interval == 0 || step == args.steps {
+            write_state(&mut out, step, &universe).map_err(write_error)?;
+        }
+    }
+
+    out.flush().map_err(write_error)
+}
+
+fn main() {
+    let result = parse_args().and_then(run);
+
+    if let Err(message) = result {
+        eprintln!("error: {}\n\n{}", message, USAGE);
+        process::exit(1);
+    }
+}
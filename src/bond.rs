@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+
+use crate::particle_types::ParticleTypes;
+use crate::preset::InvalidPreset;
+
+// Spring between the particles at indices `a` and `b`, `a < b`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bond {
+    pub a: usize,
+    pub b: usize,
+}
+
+impl Bond {
+    pub fn new(a: usize, b: usize) -> Self {
+        Self {
+            a: a.min(b),
+            b: a.max(b),
+        }
+    }
+}
+
+// Force of the spring between particles of types `a` and `b` at distance `r`,
+// positive when it pulls them together.
+pub fn spring_force(types: &ParticleTypes, a: usize, b: usize, r: f64) -> f64 {
+    let rest_length = *types.get_bond_length(a, b).unwrap() as f64;
+    let stiffness = *types.get_bond_stiffness(a, b).unwrap() as f64;
+    stiffness * (r - rest_length)
+}
+
+// Lets particles of types `a` and `b` bond, as listed in seeded presets. See
+// `ParticleTypes::set_bond` for the meaning of the values.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BondRule {
+    pub a: usize,
+    pub b: usize,
+    pub radius: f32,
+    pub rest_length: f32,
+    pub stiffness: f32,
+    #[serde(default)]
+    pub break_force: f32,
+}
+
+impl BondRule {
+    pub fn validate(&self, types: usize) -> Result<(), InvalidPreset> {
+        if self.a >= types || self.b >= types {
+            return Err(InvalidPreset::new(format!(
+                "bond between types {} and {} refers to a type beyond the {} types",
+                self.a, self.b, types
+            )));
+        }
+
+        for (name, value) in [
+            ("radius", self.radius),
+            ("rest_length", self.rest_length),
+            ("stiffness", self.stiffness),
+            ("break_force", self.break_force),
+        ] {
+            if !value.is_finite() || value < 0.0 {
+                return Err(InvalidPreset::new(format!(
+                    "bond {} ({}) must be a finite, non-negative number",
+                    name, value
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
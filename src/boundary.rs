@@ -0,0 +1,183 @@
+use serde::{Deserialize, Serialize};
+
+use crate::particle::Particle;
+use crate::shape::Circle;
+
+// Distance from the edges at which soft walls start pushing back
+const SOFT_WALL_RANGE: f64 = 40.0;
+// Acceleration of soft walls at the edges
+const SOFT_WALL_STRENGTH: f64 = 1.0;
+
+// What happens to particles at the edges of the world.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Boundary {
+    // Particles leaving one side enter from the opposite one, and interact
+    // across the edges.
+    Wrap,
+    // Particles bounce off the walls.
+    #[default]
+    Reflect,
+    // Particles touching a wall are removed.
+    Absorb,
+    // Walls push particles back, harder the closer they get.
+    Soft,
+    // Particles bounce off the largest circle that fits in the world.
+    Circle,
+    // No edges at all, the size of the world only sets where particles start.
+    Open,
+}
+
+impl Boundary {
+    pub const ALL: [Boundary; 6] = [
+        Boundary::Wrap,
+        Boundary::Reflect,
+        Boundary::Absorb,
+        Boundary::Soft,
+        Boundary::Circle,
+        Boundary::Open,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Boundary::Wrap => "Wrap",
+            Boundary::Reflect => "Reflect",
+            Boundary::Absorb => "Absorb",
+            Boundary::Soft => "Soft walls",
+            Boundary::Circle => "Circle",
+            Boundary::Open => "Open",
+        }
+    }
+
+    pub fn wraps(&self) -> bool {
+        *self == Boundary::Wrap
+    }
+
+    // Top left corner and size of the area a neighbor grid has to cover: the
+    // world, or wherever the particles are in an open one.
+    pub fn grid_area(
+        &self,
+        particles: &[Particle],
+        width: f64,
+        height: f64,
+    ) -> ((f64, f64), f64, f64) {
+        if *self == Boundary::Open {
+            bounding_box(particles)
+        } else {
+            ((0.0, 0.0), width, height)
+        }
+    }
+
+    // Arena of `Boundary::Circle`.
+    pub fn arena(width: f64, height: f64) -> Circle {
+        Circle::new(0.5 * width, 0.5 * height, 0.5 * width.min(height))
+    }
+
+    // Shortest displacement equivalent to (dx, dy), which in a wrapping world
+    // may cross the edges.
+    pub fn displacement(&self, dx: f64, dy: f64, width: f64, height: f64) -> (f64, f64) {
+        if self.wraps() {
+            (
+                dx - width * (dx / width).round(),
+                dy - height * (dy / height).round(),
+            )
+        } else {
+            (dx, dy)
+        }
+    }
+
+    // Adds the acceleration of soft walls to the acceleration of every
+    // particle in `out`.
+    pub fn apply(&self, particles: &[Particle], out: &mut [(f64, f64)], width: f64, height: f64) {
+        if *self != Boundary::Soft {
+            return;
+        }
+
+        // Grows linearly from 0 at `SOFT_WALL_RANGE` to the full strength at
+        // the edge, and beyond
+        let push = |d: f64| SOFT_WALL_STRENGTH * (1.0 - d.max(0.0) / SOFT_WALL_RANGE).max(0.0);
+
+        for (p, a) in particles.iter().zip(out.iter_mut()) {
+            a.0 += push(p.x) - push(width - p.x);
+            a.1 += push(p.y) - push(height - p.y);
+        }
+    }
+
+    // Keeps a particle within the world, `margin` being the distance its
+    // center keeps from walls. Returns whether the particle is still in it.
+    pub fn confine(&self, p: &mut Particle, margin: f64, width: f64, height: f64) -> bool {
+        match self {
+            Boundary::Wrap => {
+                p.x = p.x.rem_euclid(width);
+                p.y = p.y.rem_euclid(height);
+            }
+            // Soft walls also stop the particles pushed through them
+            Boundary::Reflect | Boundary::Soft => {
+                if p.x <= margin {
+                    p.vx = -p.vx;
+                    p.x = margin;
+                } else if p.x >= width - margin {
+                    p.vx = -p.vx;
+                    p.x = width - margin;
+                }
+
+                if p.y <= margin {
+                    p.vy = -p.vy;
+                    p.y = margin;
+                } else if p.y >= height - margin {
+                    p.vy = -p.vy;
+                    p.y = height - margin;
+                }
+            }
+            Boundary::Absorb => {
+                return p.x > margin
+                    && p.x < width - margin
+                    && p.y > margin
+                    && p.y < height - margin;
+            }
+            Boundary::Circle => {
+                let arena = Self::arena(width, height);
+                let radius = (arena.radius - margin).max(0.0);
+                let dx = p.x - arena.x;
+                let dy = p.y - arena.y;
+                let d = (dx * dx + dy * dy).sqrt();
+
+                if d >= radius && d > 0.0 {
+                    let nx = dx / d;
+                    let ny = dy / d;
+
+                    // Only reflect the velocity if it still points outwards
+                    let vn = p.vx * nx + p.vy * ny;
+                    if vn > 0.0 {
+                        p.vx -= 2.0 * vn * nx;
+                        p.vy -= 2.0 * vn * ny;
+                    }
+
+                    p.x = arena.x + radius * nx;
+                    p.y = arena.y + radius * ny;
+                }
+            }
+            Boundary::Open => {}
+        }
+
+        true
+    }
+}
+
+// Top left corner and size of the smallest rectangle containing all particles.
+fn bounding_box(particles: &[Particle]) -> ((f64, f64), f64, f64) {
+    if particles.is_empty() {
+        return ((0.0, 0.0), 0.0, 0.0);
+    }
+
+    let (mut left, mut top) = (f64::INFINITY, f64::INFINITY);
+    let (mut right, mut bottom) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for p in particles {
+        left = left.min(p.x);
+        top = top.min(p.y);
+        right = right.max(p.x);
+        bottom = bottom.max(p.y);
+    }
+
+    ((left, top), right - left, bottom - top)
+}
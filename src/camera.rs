@@ -0,0 +1,154 @@
+use crate::boundary::Boundary;
+
+const MIN_ZOOM: f32 = 0.1;
+const MAX_ZOOM: f32 = 50.0;
+
+// View onto the universe, mapping world coordinates to screen coordinates.
+#[derive(Debug, Clone)]
+pub struct Camera {
+    pub center_x: f32,
+    pub center_y: f32,
+    pub zoom: f32,
+    viewport_width: f32,
+    viewport_height: f32,
+    world_width: f32,
+    world_height: f32,
+    boundary: Boundary,
+}
+
+impl Camera {
+    pub fn new(viewport_width: f32, viewport_height: f32) -> Self {
+        Self {
+            center_x: viewport_width * 0.5,
+            center_y: viewport_height * 0.5,
+            zoom: 1.0,
+            viewport_width,
+            viewport_height,
+            world_width: viewport_width,
+            world_height: viewport_height,
+            boundary: Boundary::default(),
+        }
+    }
+
+    pub fn set_viewport(&mut self, width: f32, height: f32) {
+        self.viewport_width = width;
+        self.viewport_height = height;
+        self.clamp();
+    }
+
+    // In a wrapping or open world the camera pans endlessly, otherwise it is
+    // kept within the bounds of the world.
+    pub fn set_world(&mut self, width: f32, height: f32, boundary: Boundary) {
+        self.world_width = width;
+        self.world_height = height;
+        self.boundary = boundary;
+        self.clamp();
+    }
+
+    // Centers the whole world in the viewport.
+    pub fn reset(&mut self) {
+        self.center_x = self.world_width * 0.5;
+        self.center_y = self.world_height * 0.5;
+        self.zoom = (self.viewport_width / self.world_width)
+            .min(self.viewport_height / self.world_height)
+            .clamp(MIN_ZOOM, MAX_ZOOM);
+        self.clamp();
+    }
+
+    pub fn to_world(&self, x: f32, y: f32) -> (f32, f32) {
+        let wx = self.center_x + (x - self.viewport_width / 2.0) / self.zoom;
+        let wy = self.center_y + (y - self.viewport_height / 2.0) / self.zoom;
+        (wx, wy)
+    }
+
+    pub fn to_screen(&self, x: f32, y: f32) -> (f32, f32) {
+        let sx = (x - self.center_x) * self.zoom + self.viewport_width / 2.0;
+        let sy = (y - self.center_y) * self.zoom + self.viewport_height / 2.0;
+        (sx, sy)
+    }
+
+    // Moves the view by a distance in screen space, e.g. a mouse drag.
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        self.center_x -= dx / self.zoom;
+        self.center_y -= dy / self.zoom;
+        self.clamp();
+    }
+
+    // Zooms by `factor` while keeping the world point under the screen
+    // position (x, y) in place.
+    pub fn zoom_at(&mut self, x: f32, y: f32, factor: f32) {
+        let (wx, wy) = self.to_world(x, y);
+
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.center_x = wx - (x - self.viewport_width / 2.0) / self.zoom;
+        self.center_y = wy - (y - self.viewport_height / 2.0) / self.zoom;
+        self.clamp();
+    }
+
+    // Calls `f` with the screen position of every visible image of the world
+    // point (x, y). A wrapping world is tiled, so a point may be visible more
+    // than once, or at an offset of the world size. `margin` is in screen
+    // space, e.g. the radius of what is being drawn.
+    pub fn for_each_image<F: FnMut(f32, f32)>(&self, x: f32, y: f32, margin: f32, mut f: F) {
+        let (sx, sy) = self.to_screen(x, y);
+
+        if !self.boundary.wraps() {
+            if self.is_visible(sx, sy, margin) {
+                f(sx, sy);
+            }
+            return;
+        }
+
+        let step_x = self.world_width * self.zoom;
+        let step_y = self.world_height * self.zoom;
+
+        let first_x = ((-margin - sx) / step_x).ceil() as i32;
+        let last_x = ((self.viewport_width + margin - sx) / step_x).floor() as i32;
+        let first_y = ((-margin - sy) / step_y).ceil() as i32;
+        let last_y = ((self.viewport_height + margin - sy) / step_y).floor() as i32;
+
+        for ky in first_y..=last_y {
+            for kx in first_x..=last_x {
+                f(sx + kx as f32 * step_x, sy + ky as f32 * step_y);
+            }
+        }
+    }
+
+    fn is_visible(&self, x: f32, y: f32, margin: f32) -> bool {
+        x >= -margin
+            && x <= self.viewport_width + margin
+            && y >= -margin
+            && y <= self.viewport_height + margin
+    }
+
+    fn clamp(&mut self) {
+        match self.boundary {
+            Boundary::Wrap => {
+                self.center_x = self.center_x.rem_euclid(self.world_width);
+                self.center_y = self.center_y.rem_euclid(self.world_height);
+                return;
+            }
+            Boundary::Open => return,
+            _ => {}
+        }
+
+        // Clamp to make sure camera doesn't go out of bounds, or center the
+        // world if it is smaller than the view
+        let half_width = self.viewport_width * 0.5 / self.zoom;
+        let half_height = self.viewport_height * 0.5 / self.zoom;
+
+        self.center_x = if 2.0 * half_width >= self.world_width {
+            self.world_width * 0.5
+        } else {
+            self.center_x
+                .clamp(half_width, self.world_width - half_width)
+        };
+
+        self.center_y = if 2.0 * half_height >= self.world_height {
+            self.world_height * 0.5
+        } else {
+            self.center_y
+                .clamp(half_height, self.world_height - half_height)
+        };
+    }
+}
@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+}
+
+pub const WHITE: Color = Color::new(1.0, 1.0, 1.0, 1.0);
+
+#[cfg(feature = "render")]
+impl From<Color> for macroquad::color::Color {
+    fn from(color: Color) -> Self {
+        Self::new(color.r, color.g, color.b, color.a)
+    }
+}
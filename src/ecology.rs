@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+
+use crate::preset::InvalidPreset;
+
+// Particles of type `eater` within `Ecology::radius` of a particle of type
+// `food` take `rate` energy per step from it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Diet {
+    pub eater: usize,
+    pub food: usize,
+    pub rate: f32,
+}
+
+// Energy budget of every particle. Particles feed on others according to
+// `diets`, lose `decay` of their type per step, split in two above
+// `split_energy` and die when they run out of energy.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ecology {
+    // Energy of the initial particles, and of spawned ones
+    pub initial_energy: f32,
+    pub split_energy: f32,
+    // Distance within which particles feed
+    pub radius: f32,
+    // Energy lost per step by every type, negative for types that grow on
+    // their own, like plants
+    pub decay: Vec<f32>,
+    #[serde(default)]
+    pub diets: Vec<Diet>,
+    // Particles stop splitting at this population
+    #[serde(default = "default_max_particles")]
+    pub max_particles: usize,
+}
+
+fn default_max_particles() -> usize {
+    5000
+}
+
+impl Ecology {
+    // Decay of a type, 0 for types added after the ecology was set up.
+    pub fn decay(&self, particle_type: usize) -> f32 {
+        self.decay.get(particle_type).copied().unwrap_or(0.0)
+    }
+
+    pub fn validate(&self, types: usize) -> Result<(), InvalidPreset> {
+        for (name, value) in [
+            ("initial_energy", self.initial_energy),
+            ("split_energy", self.split_energy),
+        ] {
+            if !value.is_finite() || value <= 0.0 {
+                return Err(InvalidPreset::new(format!(
+                    "ecology {} ({}) must be greater than 0",
+                    name, value
+                )));
+            }
+        }
+
+        if !self.radius.is_finite() || self.radius < 0.0 {
+            return Err(InvalidPreset::new(format!(
+                "ecology radius ({}) must be a finite, non-negative number",
+                self.radius
+            )));
+        }
+
+        if self.decay.len() != types {
+            return Err(InvalidPreset::new(format!(
+                "ecology decay must have {} entries, found {}",
+                types,
+                self.decay.len()
+            )));
+        }
+
+        if self.decay.iter().any(|decay| !decay.is_finite()) {
+            return Err(InvalidPreset::new(
+                "ecology decay must only contain finite numbers",
+            ));
+        }
+
+        for diet in &self.diets {
+            if diet.eater >= types || diet.food >= types {
+                return Err(InvalidPreset::new(format!(
+                    "diet of type {} on type {} refers to a type beyond the {} types",
+                    diet.eater, diet.food, types
+                )));
+            }
+
+            if !diet.rate.is_finite() || diet.rate < 0.0 {
+                return Err(InvalidPreset::new(format!(
+                    "diet rate ({}) must be a finite, non-negative number",
+                    diet.rate
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
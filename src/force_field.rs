@@ -0,0 +1,308 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::boundary::Boundary;
+use crate::particle::Particle;
+use crate::preset::InvalidPreset;
+
+// An external acceleration acting on every particle, independent of its mass.
+// Positions are relative to the size of the world, (0.5, 0.5) being its
+// center, so presets work at any window size.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForceField {
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    pub kind: FieldKind,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldKind {
+    // Uniform acceleration
+    Gravity {
+        x: f32,
+        y: f32,
+    },
+    // Pulls towards (x, y), or pushes away for a negative `strength`, fading
+    // out linearly up to `radius`.
+    Point {
+        x: f32,
+        y: f32,
+        strength: f32,
+        radius: f32,
+    },
+    // Swirls around (x, y), clockwise on screen for a positive `strength`,
+    // fading out linearly up to `radius`.
+    Vortex {
+        x: f32,
+        y: f32,
+        strength: f32,
+        radius: f32,
+    },
+    // Horizontal acceleration growing linearly with the distance from the
+    // horizontal center line, in opposite directions above and below it.
+    Shear {
+        rate: f32,
+    },
+    // Pulls up the brightness gradient of a grayscale image stretched over
+    // the world, or down it for a negative `strength`.
+    Image {
+        path: PathBuf,
+        strength: f32,
+        #[serde(skip)]
+        image: Option<Arc<FieldImage>>,
+    },
+}
+
+// Brightness of every pixel of an image field or obstacle mask, between 0
+// and 1.
+#[derive(Debug)]
+pub struct FieldImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<f32>,
+}
+
+#[derive(Debug)]
+pub struct FieldImageError {
+    pub path: PathBuf,
+    pub error: image::ImageError,
+}
+
+impl fmt::Display for FieldImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.error)
+    }
+}
+
+impl std::error::Error for FieldImageError {}
+
+impl FieldImage {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, FieldImageError> {
+        let path = path.as_ref();
+        let image = image::open(path)
+            .map_err(|error| FieldImageError {
+                path: path.to_owned(),
+                error,
+            })?
+            .to_luma8();
+
+        Ok(Self {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            pixels: image.pixels().map(|p| p.0[0] as f32 / 255.0).collect(),
+        })
+    }
+
+    // Reads the image at `path`, resolving a relative path against `base`. The
+    // path is replaced with the resolved one, so that the image can be
+    // reloaded from anywhere, e.g. after restoring a snapshot.
+    pub fn load(path: &mut PathBuf, base: &Path) -> Result<Arc<Self>, FieldImageError> {
+        let resolved = base.join(&*path);
+        *path = resolved.canonicalize().unwrap_or(resolved);
+        Ok(Arc::new(FieldImage::open(&*path)?))
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Row by row, starting at the top left.
+    pub fn pixels(&self) -> &[f32] {
+        &self.pixels
+    }
+
+    fn brightness(&self, x: isize, y: isize) -> f32 {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.pixels[y * self.width + x]
+    }
+
+    // Brightness at the world position (x, y), the image being stretched over
+    // the world.
+    pub fn sample(&self, x: f64, y: f64, world_width: f64, world_height: f64) -> f32 {
+        if self.width == 0 || self.height == 0 {
+            return 0.0;
+        }
+
+        let px = (x * self.width as f64 / world_width).floor() as isize;
+        let py = (y * self.height as f64 / world_height).floor() as isize;
+        self.brightness(px, py)
+    }
+
+    // Brightness gradient at the world position (x, y), per unit of distance
+    // in the world.
+    pub fn gradient(&self, x: f64, y: f64, world_width: f64, world_height: f64) -> (f64, f64) {
+        if self.width == 0 || self.height == 0 {
+            return (0.0, 0.0);
+        }
+
+        let scale_x = self.width as f64 / world_width;
+        let scale_y = self.height as f64 / world_height;
+        let px = (x * scale_x).floor() as isize;
+        let py = (y * scale_y).floor() as isize;
+
+        let dx = self.brightness(px + 1, py) - self.brightness(px - 1, py);
+        let dy = self.brightness(px, py + 1) - self.brightness(px, py - 1);
+        (0.5 * dx as f64 * scale_x, 0.5 * dy as f64 * scale_y)
+    }
+}
+
+impl ForceField {
+    pub fn new(kind: FieldKind) -> Self {
+        Self {
+            enabled: true,
+            kind,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self.kind {
+            FieldKind::Gravity { .. } => "Gravity",
+            FieldKind::Point { .. } => "Point",
+            FieldKind::Vortex { .. } => "Vortex",
+            FieldKind::Shear { .. } => "Shear",
+            FieldKind::Image { .. } => "Image",
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), InvalidPreset> {
+        let values: &[f32] = match &self.kind {
+            FieldKind::Gravity { x, y } => &[*x, *y],
+            FieldKind::Point {
+                x,
+                y,
+                strength,
+                radius,
+            }
+            | FieldKind::Vortex {
+                x,
+                y,
+                strength,
+                radius,
+            } => {
+                if *radius <= 0.0 {
+                    return Err(InvalidPreset::new(format!(
+                        "radius of a {} field must be greater than 0",
+                        self.name().to_lowercase()
+                    )));
+                }
+                &[*x, *y, *strength, *radius]
+            }
+            FieldKind::Shear { rate } => &[*rate],
+            FieldKind::Image { strength, .. } => &[*strength],
+        };
+
+        if values.iter().any(|value| !value.is_finite()) {
+            return Err(InvalidPreset::new(format!(
+                "{} field must only contain finite numbers",
+                self.name().to_lowercase()
+            )));
+        }
+
+        Ok(())
+    }
+
+    // Reads the image of an image field, see `FieldImage::load`.
+    pub fn load_image(&mut self, base: &Path) -> Result<(), FieldImageError> {
+        if let FieldKind::Image { path, image, .. } = &mut self.kind {
+            *image = Some(FieldImage::load(path, base)?);
+        }
+
+        Ok(())
+    }
+
+    // Adds the acceleration of the field to the acceleration of every
+    // particle in `out`.
+    pub fn apply(
+        &self,
+        particles: &[Particle],
+        out: &mut [(f64, f64)],
+        width: f64,
+        height: f64,
+        boundary: Boundary,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        // Displacement from a particle to a point of the field
+        let towards = |p: &Particle, x: f32, y: f32| {
+            boundary.displacement(
+                x as f64 * width - p.x,
+                y as f64 * height - p.y,
+                width,
+                height,
+            )
+        };
+
+        // Strength at a distance, fading out linearly up to `radius`
+        let falloff = |d: f64, strength: f32, radius: f32| {
+            let radius = radius as f64;
+            if d < 1e-6 || d >= radius {
+                0.0
+            } else {
+                strength as f64 * (1.0 - d / radius)
+            }
+        };
+
+        for (p, a) in particles.iter().zip(out.iter_mut()) {
+            let (ax, ay) = match &self.kind {
+                FieldKind::Gravity { x, y } => (*x as f64, *y as f64),
+                FieldKind::Point {
+                    x,
+                    y,
+                    strength,
+                    radius,
+                } => {
+                    let (dx, dy) = towards(p, *x, *y);
+                    let d = (dx * dx + dy * dy).sqrt();
+                    let f = falloff(d, *strength, *radius);
+                    if f == 0.0 {
+                        continue;
+                    }
+                    (f * dx / d, f * dy / d)
+                }
+                FieldKind::Vortex {
+                    x,
+                    y,
+                    strength,
+                    radius,
+                } => {
+                    let (dx, dy) = towards(p, *x, *y);
+                    let d = (dx * dx + dy * dy).sqrt();
+                    let f = falloff(d, *strength, *radius);
+                    if f == 0.0 {
+                        continue;
+                    }
+                    // Perpendicular to the direction of the center
+                    (f * dy / d, -f * dx / d)
+                }
+                FieldKind::Shear { rate } => (*rate as f64 * (p.y - 0.5 * height), 0.0),
+                FieldKind::Image {
+                    strength, image, ..
+                } => match image {
+                    Some(image) => {
+                        let (gx, gy) = image.gradient(p.x, p.y, width, height);
+                        (*strength as f64 * gx, *strength as f64 * gy)
+                    }
+                    None => continue,
+                },
+            };
+
+            a.0 += ax;
+            a.1 += ay;
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+
+const R_SMOOTH: f64 = 2.0;
+// Scales the Lennard-Jones-style kernel so that its strongest attraction is
+// roughly `attract`, like the other kernels.
+const LJ_SCALE: f64 = 4.46;
+
+pub trait ForceLaw {
+    // Force towards the other particle at distance `r`, negative for
+    // repulsion. Only called for `r <= max_r`.
+    fn force(&self, r: f64, min_r: f64, max_r: f64, attract: f64) -> f64;
+}
+
+// Repulsion keeping particles from overlapping, used below `min_r`.
+fn smooth_repulsion(r: f64, min_r: f64) -> f64 {
+    R_SMOOTH * min_r * (1.0 / (min_r + R_SMOOTH) - 1.0 / (r + R_SMOOTH))
+}
+
+// Peaks with `attract` halfway between `min_r` and `max_r`.
+#[derive(Debug, Clone, Copy)]
+pub struct Triangle;
+
+impl ForceLaw for Triangle {
+    fn force(&self, r: f64, min_r: f64, max_r: f64, attract: f64) -> f64 {
+        if r > min_r {
+            let numer = 2.0 * (r - 0.5 * (max_r + min_r)).abs();
+            let denom = max_r - min_r;
+            attract * (1.0 - numer / denom)
+        } else {
+            smooth_repulsion(r, min_r)
+        }
+    }
+}
+
+// Constant `attract` between `min_r` and `max_r`.
+#[derive(Debug, Clone, Copy)]
+pub struct Flat;
+
+impl ForceLaw for Flat {
+    fn force(&self, r: f64, min_r: f64, _max_r: f64, attract: f64) -> f64 {
+        if r > min_r {
+            attract
+        } else {
+            smooth_repulsion(r, min_r)
+        }
+    }
+}
+
+// Like `Triangle`, but easing in and out of the peak.
+#[derive(Debug, Clone, Copy)]
+pub struct Smoothstep;
+
+impl ForceLaw for Smoothstep {
+    fn force(&self, r: f64, min_r: f64, max_r: f64, attract: f64) -> f64 {
+        if r > min_r {
+            let t = (r - min_r) / (max_r - min_r);
+            let u = 1.0 - (2.0 * t - 1.0).abs();
+            attract * u * u * (3.0 - 2.0 * u)
+        } else {
+            smooth_repulsion(r, min_r)
+        }
+    }
+}
+
+// Steep repulsive core and an attractive well with its equilibrium at `min_r`.
+// A negative `attract` makes it repulsive everywhere.
+#[derive(Debug, Clone, Copy)]
+pub struct LennardJones;
+
+impl ForceLaw for LennardJones {
+    fn force(&self, r: f64, min_r: f64, _max_r: f64, attract: f64) -> f64 {
+        let x = min_r / r;
+        let x7 = x.powi(7);
+        let x13 = x7 * x7 / x;
+        let f = LJ_SCALE * (attract * x7 - attract.abs() * x13);
+
+        // Limit the core to the strongest repulsion of the other kernels
+        f.max(smooth_repulsion(0.0, min_r))
+    }
+}
+
+// `attract` at `min_r`, falling off with the square of the distance until
+// the cutoff at `max_r`.
+#[derive(Debug, Clone, Copy)]
+pub struct InverseSquare;
+
+impl ForceLaw for InverseSquare {
+    fn force(&self, r: f64, min_r: f64, _max_r: f64, attract: f64) -> f64 {
+        if r > min_r {
+            let x = min_r / r;
+            attract * x * x
+        } else {
+            smooth_repulsion(r, min_r)
+        }
+    }
+}
+
+// Selection of one of the built-in force laws, as stored in presets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kernel {
+    #[default]
+    Triangle,
+    Flat,
+    Smoothstep,
+    LennardJones,
+    InverseSquare,
+}
+
+impl Kernel {
+    pub const ALL: [Kernel; 5] = [
+        Kernel::Triangle,
+        Kernel::Flat,
+        Kernel::Smoothstep,
+        Kernel::LennardJones,
+        Kernel::InverseSquare,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kernel::Triangle => "Triangle",
+            Kernel::Flat => "Flat",
+            Kernel::Smoothstep => "Smoothstep",
+            Kernel::LennardJones => "Lennard-Jones",
+            Kernel::InverseSquare => "Inverse square",
+        }
+    }
+}
+
+impl ForceLaw for Kernel {
+    fn force(&self, r: f64, min_r: f64, max_r: f64, attract: f64) -> f64 {
+        match self {
+            Kernel::Triangle => Triangle.force(r, min_r, max_r, attract),
+            Kernel::Flat => Flat.force(r, min_r, max_r, attract),
+            Kernel::Smoothstep => Smoothstep.force(r, min_r, max_r, attract),
+            Kernel::LennardJones => LennardJones.force(r, min_r, max_r, attract),
+            Kernel::InverseSquare => InverseSquare.force(r, min_r, max_r, attract),
+        }
+    }
+}
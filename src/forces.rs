@@ -0,0 +1,299 @@
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::bond::{self, Bond};
+use crate::boundary::Boundary;
+use crate::force_field::ForceField;
+use crate::force_law::ForceLaw;
+use crate::grid::SpatialGrid;
+use crate::particle::Particle;
+use crate::particle_types::ParticleTypes;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NeighborSearch {
+    // Visits every pair of particles, O(n^2).
+    BruteForce,
+    // Only visits particles in neighboring cells of a uniform grid.
+    Grid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Serial,
+    // Computes the forces on each particle in parallel. Every particle still
+    // sums its own neighbors in order, so results match `Serial` exactly.
+    Parallel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ForceMode {
+    // Every particle feels its own side of the interaction matrix, so `a` may
+    // chase `b` while `b` flees from `a`. Momentum is not conserved.
+    Asymmetric,
+    // Visits each pair once and applies equal and opposite forces, the mean of
+    // both sides of the interaction matrix. Conserves momentum, but always runs
+    // serially since each pair updates two particles.
+    Reciprocal,
+}
+
+// Settings of the universe that the forces depend on.
+#[derive(Debug, Clone, Copy)]
+pub struct ForceParams<'a> {
+    pub types: &'a ParticleTypes,
+    pub width: f64,
+    pub height: f64,
+    pub boundary: Boundary,
+    pub fields: &'a [ForceField],
+    pub brush: Option<&'a ForceField>,
+    pub bonds: &'a [Bond],
+}
+
+// Computes the acceleration of every particle for a given state, which may be
+// an intermediate state of an integrator rather than the particles of the
+// universe.
+#[derive(Debug)]
+pub struct ForceSolver {
+    pub neighbor_search: NeighborSearch,
+    pub backend: Backend,
+    pub mode: ForceMode,
+    grid: SpatialGrid,
+}
+
+impl Default for ForceSolver {
+    fn default() -> Self {
+        Self {
+            neighbor_search: NeighborSearch::Grid,
+            backend: Backend::Parallel,
+            mode: ForceMode::Asymmetric,
+            grid: SpatialGrid::new(),
+        }
+    }
+}
+
+impl ForceSolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn compute(
+        &mut self,
+        params: &ForceParams,
+        particles: &[Particle],
+        out: &mut Vec<(f64, f64)>,
+    ) {
+        if self.neighbor_search == NeighborSearch::Grid {
+            let (origin, width, height) =
+                params
+                    .boundary
+                    .grid_area(particles, params.width, params.height);
+
+            self.grid.rebuild(
+                particles,
+                origin,
+                width,
+                height,
+                params.types.largest_max_r() as f64,
+            );
+        }
+
+        let forces = Forces {
+            params,
+            particles,
+            grid: &self.grid,
+            neighbor_search: self.neighbor_search,
+        };
+
+        if self.mode == ForceMode::Reciprocal {
+            forces.reciprocal_forces(out);
+        } else {
+            out.resize(particles.len(), (0.0, 0.0));
+            match self.backend {
+                Backend::Serial => out
+                    .iter_mut()
+                    .enumerate()
+                    .for_each(|(i, a)| *a = forces.net_force(i)),
+                Backend::Parallel => out
+                    .par_iter_mut()
+                    .enumerate()
+                    .for_each(|(i, a)| *a = forces.net_force(i)),
+            }
+        }
+
+        for field in params.fields.iter().chain(params.brush) {
+            field.apply(particles, out, params.width, params.height, params.boundary);
+        }
+
+        // Springs act on both ends alike, whatever the force mode
+        for b in params.bonds {
+            let (p, q) = (&particles[b.a], &particles[b.b]);
+            let (dx, dy) =
+                params
+                    .boundary
+                    .displacement(q.x - p.x, q.y - p.y, params.width, params.height);
+            let r = (dx * dx + dy * dy).sqrt();
+            if r == 0.0 {
+                continue;
+            }
+
+            let f = bond::spring_force(params.types, p.particle_type, q.particle_type, r) / r;
+            let mass_p = *params.types.get_mass(p.particle_type).unwrap() as f64;
+            let mass_q = *params.types.get_mass(q.particle_type).unwrap() as f64;
+            out[b.a].0 += f * dx / mass_p;
+            out[b.a].1 += f * dy / mass_p;
+            out[b.b].0 -= f * dx / mass_q;
+            out[b.b].1 -= f * dy / mass_q;
+        }
+
+        params
+            .boundary
+            .apply(particles, out, params.width, params.height);
+    }
+}
+
+// Read-only view of the particles used to compute the net force on each
+// particle, so that the force pass can run independently per particle.
+struct Forces<'a> {
+    params: &'a ForceParams<'a>,
+    particles: &'a [Particle],
+    grid: &'a SpatialGrid,
+    neighbor_search: NeighborSearch,
+}
+
+impl<'a> Forces<'a> {
+    fn net_force(&self, i: usize) -> (f64, f64) {
+        let p = &self.particles[i];
+        let mut fx = 0.0;
+        let mut fy = 0.0;
+
+        let accumulate = |j: usize| {
+            // Cannot overlap
+            if i == j {
+                return;
+            }
+
+            if let Some((dfx, dfy)) = self.interaction(p, &self.particles[j]) {
+                fx += dfx;
+                fy += dfy;
+            }
+        };
+
+        match self.neighbor_search {
+            NeighborSearch::BruteForce => (0..self.particles.len()).for_each(accumulate),
+            NeighborSearch::Grid => {
+                self.grid
+                    .for_each_neighbor(p.x, p.y, self.params.boundary.wraps(), accumulate)
+            }
+        }
+
+        let mass = *self.params.types.get_mass(p.particle_type).unwrap() as f64;
+        (fx / mass, fy / mass)
+    }
+
+    // Accelerations of all particles, visiting every pair only once.
+    fn reciprocal_forces(&self, out: &mut Vec<(f64, f64)>) {
+        out.clear();
+        out.resize(self.particles.len(), (0.0, 0.0));
+
+        for (i, p) in self.particles.iter().enumerate() {
+            let mut accumulate = |j: usize| {
+                // Each pair is handled by its first particle
+                if j <= i {
+                    return;
+                }
+
+                if let Some((fx, fy)) = self.reciprocal_interaction(p, &self.particles[j]) {
+                    out[i].0 += fx;
+                    out[i].1 += fy;
+                    out[j].0 -= fx;
+                    out[j].1 -= fy;
+                }
+            };
+
+            match self.neighbor_search {
+                NeighborSearch::BruteForce => (i + 1..self.particles.len()).for_each(accumulate),
+                NeighborSearch::Grid => self.grid.for_each_neighbor(
+                    p.x,
+                    p.y,
+                    self.params.boundary.wraps(),
+                    &mut accumulate,
+                ),
+            }
+        }
+
+        for (p, a) in self.particles.iter().zip(out.iter_mut()) {
+            let mass = *self.params.types.get_mass(p.particle_type).unwrap() as f64;
+            a.0 /= mass;
+            a.1 /= mass;
+        }
+    }
+
+    // Shortest displacement from `p` to `q`.
+    fn displacement(&self, p: &Particle, q: &Particle) -> (f64, f64) {
+        self.params.boundary.displacement(
+            q.x - p.x,
+            q.y - p.y,
+            self.params.width,
+            self.params.height,
+        )
+    }
+
+    // Force felt by type `a` from type `b` at distance squared `r2`, or `None`
+    // if it is out of range.
+    fn force(&self, a: usize, b: usize, r2: f64) -> Option<f64> {
+        let types = self.params.types;
+        let min_r = *types.get_min_r(a, b).unwrap() as f64;
+        let max_r = *types.get_max_r(a, b).unwrap() as f64;
+
+        if r2 > max_r * max_r {
+            return None;
+        }
+
+        let r = r2.sqrt();
+        let attract = *types.get_attract(a, b).unwrap() as f64;
+        let kernel = types.get_kernel(a, b).unwrap();
+        Some(kernel.force(r, min_r, max_r, attract))
+    }
+
+    // Force applied on `p` by `q`, or `None` if `q` is out of range.
+    fn interaction(&self, p: &Particle, q: &Particle) -> Option<(f64, f64)> {
+        let (mut dx, mut dy) = self.displacement(p, q);
+
+        // Get distance squared
+        let r2 = dx * dx + dy * dy;
+        if r2 < 0.01 {
+            return None;
+        }
+
+        // Normalize displacement
+        let r = r2.sqrt();
+        dx /= r;
+        dy /= r;
+
+        let f = self.force(p.particle_type, q.particle_type, r2)?;
+        Some((f * dx, f * dy))
+    }
+
+    // Force applied on `p` by `q`, and the opposite on `q` by `p`, or `None`
+    // if they are out of range of each other.
+    fn reciprocal_interaction(&self, p: &Particle, q: &Particle) -> Option<(f64, f64)> {
+        let (mut dx, mut dy) = self.displacement(p, q);
+
+        let r2 = dx * dx + dy * dy;
+        if r2 < 0.01 {
+            return None;
+        }
+
+        let r = r2.sqrt();
+        dx /= r;
+        dy /= r;
+
+        let f = match (
+            self.force(p.particle_type, q.particle_type, r2),
+            self.force(q.particle_type, p.particle_type, r2),
+        ) {
+            (None, None) => return None,
+            (a, b) => 0.5 * (a.unwrap_or(0.0) + b.unwrap_or(0.0)),
+        };
+        Some((f * dx, f * dy))
+    }
+}
@@ -0,0 +1,113 @@
+use crate::particle::Particle;
+
+// Limits the memory used by the grid when particles spread out in an open
+// world, at the cost of larger cells.
+const MAX_CELLS_PER_AXIS: usize = 1024;
+
+// Uniform grid of square-ish cells, each at least `cell_size` wide, so that
+// all neighbors within `cell_size` of a particle are found in the 3x3 block
+// of cells around it.
+#[derive(Debug, Default)]
+pub struct SpatialGrid {
+    // Top left corner of the area covered by the cells
+    left: f64,
+    top: f64,
+    cols: usize,
+    rows: usize,
+    cell_width: f64,
+    cell_height: f64,
+    // Start offset of each cell into `indices` (counting sort layout).
+    cell_start: Vec<usize>,
+    indices: Vec<usize>,
+}
+
+impl SpatialGrid {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Sorts the particles into cells covering the area from (left, top) to
+    // (left + width, top + height).
+    pub fn rebuild(
+        &mut self,
+        particles: &[Particle],
+        (left, top): (f64, f64),
+        width: f64,
+        height: f64,
+        cell_size: f64,
+    ) {
+        let cell_size = cell_size.max(1.0);
+
+        self.left = left;
+        self.top = top;
+        self.cols = ((width / cell_size).floor() as usize).clamp(1, MAX_CELLS_PER_AXIS);
+        self.rows = ((height / cell_size).floor() as usize).clamp(1, MAX_CELLS_PER_AXIS);
+        self.cell_width = width / self.cols as f64;
+        self.cell_height = height / self.rows as f64;
+
+        let num_cells = self.cols * self.rows;
+        self.cell_start.clear();
+        self.cell_start.resize(num_cells + 1, 0);
+
+        for p in particles {
+            let cell = self.cell_index(p.x, p.y);
+            self.cell_start[cell + 1] += 1;
+        }
+
+        for cell in 0..num_cells {
+            self.cell_start[cell + 1] += self.cell_start[cell];
+        }
+
+        let mut fill = self.cell_start.clone();
+        self.indices.clear();
+        self.indices.resize(particles.len(), 0);
+
+        for (i, p) in particles.iter().enumerate() {
+            let cell = self.cell_index(p.x, p.y);
+            self.indices[fill[cell]] = i;
+            fill[cell] += 1;
+        }
+    }
+
+    // Calls `f` with the index of every particle in the cells surrounding
+    // (x, y), including the particle at (x, y) itself.
+    pub fn for_each_neighbor<F: FnMut(usize)>(&self, x: f64, y: f64, wrap: bool, mut f: F) {
+        let (cx, cy) = self.cell_coords(x, y);
+
+        for row in Self::neighbor_range(cy, self.rows, wrap) {
+            for col in Self::neighbor_range(cx, self.cols, wrap) {
+                let cell = row * self.cols + col;
+                for &index in &self.indices[self.cell_start[cell]..self.cell_start[cell + 1]] {
+                    f(index);
+                }
+            }
+        }
+    }
+
+    fn cell_coords(&self, x: f64, y: f64) -> (usize, usize) {
+        // Particles outside the bounds are clamped into the border cells
+        let cx = (((x - self.left) / self.cell_width).floor().max(0.0) as usize).min(self.cols - 1);
+        let cy = (((y - self.top) / self.cell_height).floor().max(0.0) as usize).min(self.rows - 1);
+        (cx, cy)
+    }
+
+    fn cell_index(&self, x: f64, y: f64) -> usize {
+        let (cx, cy) = self.cell_coords(x, y);
+        cy * self.cols + cx
+    }
+
+    // Cells to visit along one axis. With fewer than three cells every cell is
+    // already a neighbor, which also avoids visiting a wrapped cell twice.
+    fn neighbor_range(c: usize, len: usize, wrap: bool) -> impl Iterator<Item = usize> {
+        let (start, count) = if len < 3 {
+            (0, len)
+        } else if wrap {
+            (c + len - 1, 3)
+        } else {
+            let start = c.saturating_sub(1);
+            (start, (c + 2).min(len) - start)
+        };
+
+        (start..start + count).map(move |c| c % len)
+    }
+}
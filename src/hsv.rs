@@ -1,8 +1,8 @@
-pub trait HSV {
+pub trait Hsv {
     fn from_hsv(h: f32, s: f32, v: f32) -> Self;
 }
 
-impl HSV for macroquad::color::Color {
+impl Hsv for crate::color::Color {
     fn from_hsv(h: f32, s: f32, v: f32) -> Self {
         let i = (h * 6.0).round();
         let f = h * 6.0 - i;
@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+
+use crate::particle::Particle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Integrator {
+    // Updates the velocity first and moves with the new velocity. This is the
+    // scheme of the original implementation.
+    #[default]
+    SymplecticEuler,
+    // Second order and time reversible, two force evaluations per step.
+    VelocityVerlet,
+    // Classic fourth order Runge-Kutta, four force evaluations per step.
+    Rk4,
+}
+
+// Buffers reused between steps.
+#[derive(Debug, Default)]
+pub struct IntegratorScratch {
+    accelerations: [Vec<(f64, f64)>; 4],
+    particles: Vec<Particle>,
+}
+
+impl Integrator {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Integrator::SymplecticEuler => "Symplectic Euler",
+            Integrator::VelocityVerlet => "Velocity Verlet",
+            Integrator::Rk4 => "RK4",
+        }
+    }
+
+    // Advances positions and velocities by `dt`. `accelerations` fills its
+    // output with the acceleration of every particle in the given state.
+    pub fn integrate<F>(
+        &self,
+        particles: &mut [Particle],
+        dt: f64,
+        scratch: &mut IntegratorScratch,
+        mut accelerations: F,
+    ) where
+        F: FnMut(&[Particle], &mut Vec<(f64, f64)>),
+    {
+        let [a1, a2, a3, a4] = &mut scratch.accelerations;
+
+        match self {
+            Integrator::SymplecticEuler => {
+                accelerations(particles, a1);
+
+                for (p, &(ax, ay)) in particles.iter_mut().zip(a1.iter()) {
+                    p.vx += ax * dt;
+                    p.vy += ay * dt;
+                    p.x += p.vx * dt;
+                    p.y += p.vy * dt;
+                }
+            }
+            Integrator::VelocityVerlet => {
+                accelerations(particles, a1);
+
+                for (p, &(ax, ay)) in particles.iter_mut().zip(a1.iter()) {
+                    p.x += p.vx * dt + 0.5 * ax * dt * dt;
+                    p.y += p.vy * dt + 0.5 * ay * dt * dt;
+                }
+
+                accelerations(particles, a2);
+
+                for ((p, &(ax, ay)), &(bx, by)) in
+                    particles.iter_mut().zip(a1.iter()).zip(a2.iter())
+                {
+                    p.vx += 0.5 * (ax + bx) * dt;
+                    p.vy += 0.5 * (ay + by) * dt;
+                }
+            }
+            Integrator::Rk4 => {
+                let stage = &mut scratch.particles;
+
+                // k1: derivatives at the start of the step
+                accelerations(particles, a1);
+
+                // k2: at the midpoint, following k1
+                offset(stage, particles, 0.5 * dt, |_, p| (p.vx, p.vy));
+                accelerations(stage, a2);
+
+                // k3: at the midpoint, following k2
+                offset(stage, particles, 0.5 * dt, |i, p| {
+                    (p.vx + a1[i].0 * 0.5 * dt, p.vy + a1[i].1 * 0.5 * dt)
+                });
+                accelerations(stage, a3);
+
+                // k4: at the end of the step, following k3
+                offset(stage, particles, dt, |i, p| {
+                    (p.vx + a2[i].0 * 0.5 * dt, p.vy + a2[i].1 * 0.5 * dt)
+                });
+                accelerations(stage, a4);
+
+                for (i, p) in particles.iter_mut().enumerate() {
+                    // Velocities at each stage, the derivatives of position
+                    let k2 = (p.vx + a1[i].0 * 0.5 * dt, p.vy + a1[i].1 * 0.5 * dt);
+                    let k3 = (p.vx + a2[i].0 * 0.5 * dt, p.vy + a2[i].1 * 0.5 * dt);
+                    let k4 = (p.vx + a3[i].0 * dt, p.vy + a3[i].1 * dt);
+
+                    p.x += dt / 6.0 * (p.vx + 2.0 * k2.0 + 2.0 * k3.0 + k4.0);
+                    p.y += dt / 6.0 * (p.vy + 2.0 * k2.1 + 2.0 * k3.1 + k4.1);
+                    p.vx += dt / 6.0 * (a1[i].0 + 2.0 * a2[i].0 + 2.0 * a3[i].0 + a4[i].0);
+                    p.vy += dt / 6.0 * (a1[i].1 + 2.0 * a2[i].1 + 2.0 * a3[i].1 + a4[i].1);
+                }
+            }
+        }
+    }
+}
+
+// Fills `stage` with `particles` moved by `h` times the velocity returned by
+// `velocity` for each particle.
+fn offset<F>(stage: &mut Vec<Particle>, particles: &[Particle], h: f64, velocity: F)
+where
+    F: Fn(usize, &Particle) -> (f64, f64),
+{
+    stage.clear();
+    stage.extend(particles.iter().enumerate().map(|(i, p)| {
+        let (vx, vy) = velocity(i, p);
+        Particle {
+            x: p.x + vx * h,
+            y: p.y + vy * h,
+            ..p.clone()
+        }
+    }));
+}
@@ -0,0 +1,21 @@
+pub mod bond;
+pub mod boundary;
+pub mod camera;
+pub mod color;
+pub mod ecology;
+pub mod force_field;
+pub mod force_law;
+pub mod forces;
+pub mod grid;
+pub mod hsv;
+pub mod integrator;
+pub mod obstacle;
+pub mod particle;
+pub mod particle_types;
+pub mod preset;
+pub mod preset_library;
+pub mod reaction;
+pub mod shape;
+pub mod snapshot;
+pub mod thermostat;
+pub mod universe;
@@ -2,18 +2,20 @@
 
 mod app;
 mod counter;
-mod hsv;
-mod particle;
-mod particle_types;
-mod preset;
-mod universe;
 
 use macroquad::window::*;
 
-use app::App;
+use app::{App, PHYSICS_DELTA_TIME};
 
-const PHYSICS_SIMULATION_FPS: u32 = 100;
-const PHYSICS_DELTA_TIME: f64 = 1.0 / PHYSICS_SIMULATION_FPS as f64;
+// Enough for the fastest speed at 60 FPS. Slower frames drop the time they
+// could not simulate, rather than falling further behind every frame.
+const MAX_TICKS_PER_FRAME: u32 = 300;
+
+const PRESETS_DIR: &str = "presets";
+
+fn arg_value(name: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != name).nth(1)
+}
 
 fn window_conf() -> Conf {
     Conf {
@@ -32,15 +34,18 @@ async fn main() {
     let w = screen_width() as usize;
     let h = screen_height() as usize;
 
-    let mut app = App::new(w as f32, h as f32);
+    // Optional `--seed <N>` to reproduce a previously seen world
+    let rng_seed =
+        arg_value("--seed").map(|seed| seed.parse().expect("--seed must be an unsigned integer"));
+    // Directory with user presets, in addition to the built-in ones
+    let presets_dir = arg_value("--presets").unwrap_or_else(|| PRESETS_DIR.to_owned());
+
+    let mut app = App::new(w as f32, h as f32, &presets_dir, rng_seed);
 
     let mut accumulator = 0.0;
     let mut t = 0.0;
     let dt = PHYSICS_DELTA_TIME;
 
-    let mut previous_state: f64 = 0.0;
-    let current_state: f64 = 0.0;
-
     loop {
         let mut frame_time = macroquad::time::get_frame_time() as f64;
 
@@ -48,19 +53,34 @@ async fn main() {
             frame_time = 0.25;
         }
 
-        accumulator += frame_time;
+        app.handle_input();
+
+        // Scaling the time fed into the accumulator gives fractional speeds
+        // while the physics keep their fixed timestep
+        accumulator += frame_time * app.speed();
 
-        while accumulator >= dt {
-            previous_state = current_state;
-            app.update(t, dt * 2.0);
+        let mut ticks = 0;
+        while accumulator >= dt && ticks < MAX_TICKS_PER_FRAME {
+            app.update(t, dt);
             t += dt;
             accumulator -= dt;
+            ticks += 1;
+        }
+
+        if ticks == MAX_TICKS_PER_FRAME {
+            accumulator %= dt;
         }
 
-        let alpha = accumulator / dt;
-        let state = current_state * alpha + previous_state * (1.0 - alpha);
+        // Interpolate between the last two physics states when rendering,
+        // except while paused, where stepping shows the actual state
+        let alpha = if app.paused() {
+            accumulator = 0.0;
+            1.0
+        } else {
+            accumulator / dt
+        };
 
-        app.draw(state, alpha);
+        app.draw(alpha);
 
         next_frame().await;
     }
@@ -0,0 +1,227 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::force_field::{FieldImage, FieldImageError};
+use crate::particle::Particle;
+use crate::preset::InvalidPreset;
+use crate::shape::{Polygon, Segment};
+
+// Pixels of a mask darker than this are solid
+pub const MASK_THRESHOLD: f32 = 0.5;
+
+// Static shape that particles bounce off. Like force fields, positions are
+// relative to the size of the world, while radii are in world units.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Obstacle {
+    Circle {
+        x: f32,
+        y: f32,
+        radius: f32,
+    },
+    // Wall without thickness from (x1, y1) to (x2, y2)
+    Segment {
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+    },
+    // Solid closed polygon
+    Polygon {
+        points: Vec<[f32; 2]>,
+    },
+    // Dark pixels of a grayscale image stretched over the world are solid.
+    Mask {
+        path: PathBuf,
+        #[serde(skip)]
+        image: Option<Arc<FieldImage>>,
+    },
+}
+
+impl Obstacle {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Obstacle::Circle { .. } => "Circle",
+            Obstacle::Segment { .. } => "Segment",
+            Obstacle::Polygon { .. } => "Polygon",
+            Obstacle::Mask { .. } => "Mask",
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), InvalidPreset> {
+        let values: Vec<f32> = match self {
+            Obstacle::Circle { x, y, radius } => {
+                if *radius <= 0.0 {
+                    return Err(InvalidPreset::new(
+                        "radius of a circle obstacle must be greater than 0",
+                    ));
+                }
+                vec![*x, *y, *radius]
+            }
+            Obstacle::Segment { x1, y1, x2, y2 } => vec![*x1, *y1, *x2, *y2],
+            Obstacle::Polygon { points } => {
+                if points.len() < 3 {
+                    return Err(InvalidPreset::new(
+                        "polygon obstacle must have at least 3 points",
+                    ));
+                }
+                points.iter().flatten().copied().collect()
+            }
+            Obstacle::Mask { .. } => Vec::new(),
+        };
+
+        if values.iter().any(|value| !value.is_finite()) {
+            return Err(InvalidPreset::new(format!(
+                "{} obstacle must only contain finite numbers",
+                self.name().to_lowercase()
+            )));
+        }
+
+        Ok(())
+    }
+
+    // Reads the image of a mask, see `FieldImage::load`.
+    pub fn load_image(&mut self, base: &Path) -> Result<(), FieldImageError> {
+        if let Obstacle::Mask { path, image } = self {
+            *image = Some(FieldImage::load(path, base)?);
+        }
+
+        Ok(())
+    }
+
+    // Pushes a particle that moved from `previous` into the obstacle back out
+    // and bounces it off the surface. `margin` is the distance its center
+    // keeps from the obstacle, except for masks, which only stop it from
+    // entering a solid pixel.
+    pub fn collide(
+        &self,
+        p: &mut Particle,
+        previous: (f64, f64),
+        margin: f64,
+        width: f64,
+        height: f64,
+    ) {
+        let point = |x: f32, y: f32| (x as f64 * width, y as f64 * height);
+
+        match self {
+            Obstacle::Circle { x, y, radius } => {
+                let (cx, cy) = point(*x, *y);
+                let min_d = *radius as f64 + margin;
+                let dx = p.x - cx;
+                let dy = p.y - cy;
+                let d = (dx * dx + dy * dy).sqrt();
+                if d >= min_d {
+                    return;
+                }
+
+                let n = if d > 0.0 {
+                    (dx / d, dy / d)
+                } else {
+                    (1.0, 0.0)
+                };
+                bounce(p, (cx + n.0 * min_d, cy + n.1 * min_d), n);
+            }
+            Obstacle::Segment { x1, y1, x2, y2 } => {
+                let segment = Segment::new(point(*x1, *y1), point(*x2, *y2));
+                collide_segment(p, previous, segment, margin);
+            }
+            Obstacle::Polygon { points } => {
+                let polygon = Polygon::new(points.iter().map(|&[x, y]| point(x, y)).collect());
+
+                if !polygon.contains(previous.0, previous.1) {
+                    for edge in polygon.edges() {
+                        collide_segment(p, previous, edge, margin);
+                    }
+                } else if polygon.contains(p.x, p.y) {
+                    // Was already inside, e.g. when the polygon was drawn over
+                    // it, so leave through the closest edge
+                    let closest = polygon
+                        .edges()
+                        .map(|edge| edge.closest_point(p.x, p.y))
+                        .min_by(|a, b| {
+                            let da = (a.0 - p.x).powi(2) + (a.1 - p.y).powi(2);
+                            let db = (b.0 - p.x).powi(2) + (b.1 - p.y).powi(2);
+                            da.total_cmp(&db)
+                        })
+                        .unwrap();
+
+                    let dx = closest.0 - p.x;
+                    let dy = closest.1 - p.y;
+                    let d = (dx * dx + dy * dy).sqrt();
+                    let n = if d > 0.0 {
+                        (dx / d, dy / d)
+                    } else {
+                        (1.0, 0.0)
+                    };
+                    bounce(p, (closest.0 + n.0 * margin, closest.1 + n.1 * margin), n);
+                }
+            }
+            Obstacle::Mask {
+                image: Some(image), ..
+            } => {
+                let solid = |x: f64, y: f64| image.sample(x, y, width, height) < MASK_THRESHOLD;
+
+                // Nowhere to push a particle that started inside
+                if !solid(p.x, p.y) || solid(previous.0, previous.1) {
+                    return;
+                }
+
+                // Up the brightness gradient is out of the solid pixels
+                let (gx, gy) = image.gradient(previous.0, previous.1, width, height);
+                let g = (gx * gx + gy * gy).sqrt();
+                let n = if g > 0.0 {
+                    (gx / g, gy / g)
+                } else {
+                    let dx = previous.0 - p.x;
+                    let dy = previous.1 - p.y;
+                    let d = (dx * dx + dy * dy).sqrt();
+                    (dx / d, dy / d)
+                };
+
+                bounce(p, previous, n);
+            }
+            Obstacle::Mask { image: None, .. } => {}
+        }
+    }
+}
+
+// Keeps a particle at least `margin` away from a segment, on the side it came
+// from if it went through.
+fn collide_segment(p: &mut Particle, previous: (f64, f64), segment: Segment, margin: f64) {
+    let closest = segment.closest_point(p.x, p.y);
+    let path = Segment::new(previous, (p.x, p.y));
+
+    let n = if path.crosses(&segment) {
+        segment.normal_towards(previous.0, previous.1)
+    } else {
+        let dx = p.x - closest.0;
+        let dy = p.y - closest.1;
+        let d = (dx * dx + dy * dy).sqrt();
+        if d >= margin {
+            return;
+        }
+
+        if d > 0.0 {
+            (dx / d, dy / d)
+        } else {
+            segment.normal_towards(previous.0, previous.1)
+        }
+    };
+
+    bounce(p, (closest.0 + n.0 * margin, closest.1 + n.1 * margin), n);
+}
+
+// Moves a particle to `position` on the outside of a surface with the unit
+// normal `n`, reflecting its velocity if it points into the surface.
+fn bounce(p: &mut Particle, position: (f64, f64), n: (f64, f64)) {
+    p.x = position.0;
+    p.y = position.1;
+
+    let vn = p.vx * n.0 + p.vy * n.1;
+    if vn < 0.0 {
+        p.vx -= 2.0 * vn * n.0;
+        p.vy -= 2.0 * vn * n.1;
+    }
+}
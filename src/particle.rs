@@ -1,8 +1,13 @@
-#[derive(Debug, Default, Clone)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Particle {
     pub x: f64,
     pub y: f64,
     pub vx: f64,
     pub vy: f64,
     pub particle_type: usize,
+    // Only used with an `Ecology`
+    #[serde(default)]
+    pub energy: f64,
 }
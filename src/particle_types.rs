@@ -1,11 +1,45 @@
-use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+use crate::color::{Color, WHITE};
+use crate::force_law::Kernel;
+use crate::preset::InvalidPreset;
+use crate::reaction::Reaction;
+
+pub const DEFAULT_MASS: f32 = 1.0;
+pub const DEFAULT_RADIUS: f32 = 5.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ParticleTypes {
     colors: Vec<Color>,
     attract: Vec<f32>,
     min_r: Vec<f32>,
     max_r: Vec<f32>,
+    #[serde(default)]
+    kernel: Vec<Kernel>,
+    #[serde(default)]
+    mass: Vec<f32>,
+    #[serde(default)]
+    radius: Vec<f32>,
+    #[serde(default)]
+    friction: Vec<f32>,
+    #[serde(default)]
+    temperature: Vec<f32>,
+    // Particles of two types closer than `bond_r` bond with a spring, see
+    // `set_bond`
+    #[serde(default)]
+    bond_r: Vec<f32>,
+    #[serde(default)]
+    bond_length: Vec<f32>,
+    #[serde(default)]
+    bond_stiffness: Vec<f32>,
+    #[serde(default)]
+    bond_break: Vec<f32>,
+    // Bonds per particle of each type, 0 for no limit
+    #[serde(default)]
+    max_bonds: Vec<usize>,
+    // Evaluated in order every step, see `Universe::step`
+    #[serde(default)]
+    reactions: Vec<Reaction>,
 }
 
 impl ParticleTypes {
@@ -15,6 +49,17 @@ impl ParticleTypes {
             attract: vec![0.0; size * size],
             min_r: vec![0.0; size * size],
             max_r: vec![0.0; size * size],
+            kernel: vec![Kernel::default(); size * size],
+            mass: vec![DEFAULT_MASS; size],
+            radius: vec![DEFAULT_RADIUS; size],
+            friction: vec![0.0; size],
+            temperature: vec![0.0; size],
+            bond_r: vec![0.0; size * size],
+            bond_length: vec![0.0; size * size],
+            bond_stiffness: vec![0.0; size * size],
+            bond_break: vec![0.0; size * size],
+            max_bonds: vec![0; size],
+            reactions: Vec::new(),
         }
     }
 
@@ -23,6 +68,33 @@ impl ParticleTypes {
         self.attract.resize(size * size, 0.0);
         self.min_r.resize(size * size, 0.0);
         self.max_r.resize(size * size, 0.0);
+        self.kernel.resize(size * size, Kernel::default());
+        self.mass.resize(size, DEFAULT_MASS);
+        self.radius.resize(size, DEFAULT_RADIUS);
+        self.friction.resize(size, 0.0);
+        self.temperature.resize(size, 0.0);
+        self.bond_r.resize(size * size, 0.0);
+        self.bond_length.resize(size * size, 0.0);
+        self.bond_stiffness.resize(size * size, 0.0);
+        self.bond_break.resize(size * size, 0.0);
+        self.max_bonds.resize(size, 0);
+        self.reactions.retain(|reaction| reaction.fits(size));
+    }
+
+    // Properties added after a file was written deserialize as empty, and get
+    // their defaults. Anything else of the wrong size is left for `validate`.
+    pub fn fill_defaults(&mut self) {
+        let size = self.size();
+        fill_empty(&mut self.kernel, size * size, Kernel::default());
+        fill_empty(&mut self.mass, size, DEFAULT_MASS);
+        fill_empty(&mut self.radius, size, DEFAULT_RADIUS);
+        fill_empty(&mut self.friction, size, 0.0);
+        fill_empty(&mut self.temperature, size, 0.0);
+        fill_empty(&mut self.bond_r, size * size, 0.0);
+        fill_empty(&mut self.bond_length, size * size, 0.0);
+        fill_empty(&mut self.bond_stiffness, size * size, 0.0);
+        fill_empty(&mut self.bond_break, size * size, 0.0);
+        fill_empty(&mut self.max_bonds, size, 0);
     }
 
     pub fn size(&self) -> usize {
@@ -42,6 +114,65 @@ impl ParticleTypes {
         *color = value;
     }
 
+    pub fn get_mass(&self, index: usize) -> Option<&f32> {
+        self.mass.get(index)
+    }
+
+    pub fn set_mass(&mut self, index: usize, value: f32) {
+        let mass = self.mass.get_mut(index).expect("no mass at index");
+        *mass = value;
+    }
+
+    pub fn get_radius(&self, index: usize) -> Option<&f32> {
+        self.radius.get(index)
+    }
+
+    pub fn set_radius(&mut self, index: usize, value: f32) {
+        let radius = self.radius.get_mut(index).expect("no radius at index");
+        *radius = value;
+    }
+
+    pub fn get_friction(&self, index: usize) -> Option<&f32> {
+        self.friction.get(index)
+    }
+
+    pub fn set_friction(&mut self, index: usize, value: f32) {
+        let friction = self.friction.get_mut(index).expect("no friction at index");
+        *friction = value;
+    }
+
+    pub fn get_temperature(&self, index: usize) -> Option<&f32> {
+        self.temperature.get(index)
+    }
+
+    pub fn set_temperature(&mut self, index: usize, value: f32) {
+        let temperature = self
+            .temperature
+            .get_mut(index)
+            .expect("no temperature at index");
+        *temperature = value;
+    }
+
+    pub fn get_max_bonds(&self, index: usize) -> Option<&usize> {
+        self.max_bonds.get(index)
+    }
+
+    pub fn set_max_bonds(&mut self, index: usize, value: usize) {
+        let max_bonds = self
+            .max_bonds
+            .get_mut(index)
+            .expect("no max_bonds at index");
+        *max_bonds = value;
+    }
+
+    pub fn reactions(&self) -> &[Reaction] {
+        &self.reactions
+    }
+
+    pub fn set_reactions(&mut self, reactions: Vec<Reaction>) {
+        self.reactions = reactions;
+    }
+
     pub fn get_attract(&self, i: usize, j: usize) -> Option<&f32> {
         self.attract.get(i * self.size() + j)
     }
@@ -71,4 +202,204 @@ impl ParticleTypes {
         let max_r = self.max_r.get_mut(index).expect("no max_r at index");
         *max_r = value;
     }
+
+    pub fn get_kernel(&self, i: usize, j: usize) -> Option<&Kernel> {
+        self.kernel.get(i * self.size() + j)
+    }
+
+    pub fn set_kernel(&mut self, i: usize, j: usize, value: Kernel) {
+        let index = i * self.size() + j;
+        let kernel = self.kernel.get_mut(index).expect("no kernel at index");
+        *kernel = value;
+    }
+
+    pub fn get_bond_r(&self, i: usize, j: usize) -> Option<&f32> {
+        self.bond_r.get(i * self.size() + j)
+    }
+
+    pub fn get_bond_length(&self, i: usize, j: usize) -> Option<&f32> {
+        self.bond_length.get(i * self.size() + j)
+    }
+
+    pub fn get_bond_stiffness(&self, i: usize, j: usize) -> Option<&f32> {
+        self.bond_stiffness.get(i * self.size() + j)
+    }
+
+    pub fn get_bond_break(&self, i: usize, j: usize) -> Option<&f32> {
+        self.bond_break.get(i * self.size() + j)
+    }
+
+    // Particles of types `i` and `j` closer than `radius` bond with a spring
+    // of `stiffness` pulling them to `rest_length` apart, which breaks when
+    // it pulls or pushes harder than `break_force`, or never if it is 0. A
+    // `radius` of 0 keeps them from bonding. Bonds are symmetric, so this
+    // sets both `(i, j)` and `(j, i)`.
+    pub fn set_bond(
+        &mut self,
+        i: usize,
+        j: usize,
+        radius: f32,
+        rest_length: f32,
+        stiffness: f32,
+        break_force: f32,
+    ) {
+        let size = self.size();
+        assert!(i < size && j < size, "no bond at index");
+
+        for index in [i * size + j, j * size + i] {
+            self.bond_r[index] = radius;
+            self.bond_length[index] = rest_length;
+            self.bond_stiffness[index] = stiffness;
+            self.bond_break[index] = break_force;
+        }
+    }
+
+    pub fn largest_max_r(&self) -> f32 {
+        self.max_r.iter().cloned().fold(0.0, f32::max)
+    }
+
+    pub fn largest_bond_r(&self) -> f32 {
+        self.bond_r.iter().cloned().fold(0.0, f32::max)
+    }
+
+    // Checks matrices loaded from a file before they are indexed into.
+    pub fn validate(&self) -> Result<(), InvalidPreset> {
+        let size = self.size();
+
+        if size == 0 {
+            return Err(InvalidPreset::new("types must not be empty"));
+        }
+
+        for (name, matrix) in [
+            ("attract", &self.attract),
+            ("min_r", &self.min_r),
+            ("max_r", &self.max_r),
+            ("bond_r", &self.bond_r),
+            ("bond_length", &self.bond_length),
+            ("bond_stiffness", &self.bond_stiffness),
+            ("bond_break", &self.bond_break),
+        ] {
+            if matrix.len() != size * size {
+                return Err(InvalidPreset::new(format!(
+                    "{} must have {} entries for {} types, found {}",
+                    name,
+                    size * size,
+                    size,
+                    matrix.len()
+                )));
+            }
+
+            if matrix.iter().any(|value| !value.is_finite()) {
+                return Err(InvalidPreset::new(format!(
+                    "{} must only contain finite numbers",
+                    name
+                )));
+            }
+        }
+
+        if self.kernel.len() != size * size {
+            return Err(InvalidPreset::new(format!(
+                "kernel must have {} entries for {} types, found {}",
+                size * size,
+                size,
+                self.kernel.len()
+            )));
+        }
+
+        for (name, values) in [
+            ("mass", &self.mass),
+            ("radius", &self.radius),
+            ("friction", &self.friction),
+            ("temperature", &self.temperature),
+        ] {
+            if values.len() != size {
+                return Err(InvalidPreset::new(format!(
+                    "{} must have {} entries, found {}",
+                    name,
+                    size,
+                    values.len()
+                )));
+            }
+
+            if values
+                .iter()
+                .any(|value| !value.is_finite() || *value < 0.0)
+            {
+                return Err(InvalidPreset::new(format!(
+                    "{} must only contain finite, non-negative numbers",
+                    name
+                )));
+            }
+        }
+
+        if self.max_bonds.len() != size {
+            return Err(InvalidPreset::new(format!(
+                "max_bonds must have {} entries, found {}",
+                size,
+                self.max_bonds.len()
+            )));
+        }
+
+        for (name, matrix) in [
+            ("bond_r", &self.bond_r),
+            ("bond_length", &self.bond_length),
+            ("bond_stiffness", &self.bond_stiffness),
+            ("bond_break", &self.bond_break),
+        ] {
+            if let Some(index) = (0..size * size).find(|&i| matrix[i] < 0.0) {
+                return Err(InvalidPreset::new(format!(
+                    "{} ({}) must not be negative for types {} and {}",
+                    name,
+                    matrix[index],
+                    index / size,
+                    index % size
+                )));
+            }
+
+            if let Some(index) =
+                (0..size * size).find(|&i| matrix[i] != matrix[i % size * size + i / size])
+            {
+                return Err(InvalidPreset::new(format!(
+                    "{} must be the same for types {} and {} both ways",
+                    name,
+                    index / size,
+                    index % size
+                )));
+            }
+        }
+
+        if let Some(index) = self.mass.iter().position(|&mass| mass == 0.0) {
+            return Err(InvalidPreset::new(format!(
+                "mass of type {} must be greater than 0",
+                index
+            )));
+        }
+
+        if let Some(index) = self.friction.iter().position(|&friction| friction > 1.0) {
+            return Err(InvalidPreset::new(format!(
+                "friction of type {} ({}) must not be greater than 1",
+                index, self.friction[index]
+            )));
+        }
+
+        if let Some(index) = (0..size * size).find(|&i| self.min_r[i] > self.max_r[i]) {
+            return Err(InvalidPreset::new(format!(
+                "min_r ({}) must not be greater than max_r ({}) for types {} and {}",
+                self.min_r[index],
+                self.max_r[index],
+                index / size,
+                index % size
+            )));
+        }
+
+        self.reactions
+            .iter()
+            .try_for_each(|reaction| reaction.validate(size))
+    }
+}
+
+fn fill_empty<T: Clone>(values: &mut Vec<T>, len: usize, value: T) {
+    if values.is_empty() {
+        values.resize(len, value);
+    }
 }
@@ -1,12 +1,25 @@
+use std::fmt;
+
 use phf::phf_ordered_map;
+use serde::{Deserialize, Serialize};
+
+use crate::bond::BondRule;
+use crate::boundary::Boundary;
+use crate::ecology::Ecology;
+use crate::force_field::ForceField;
+use crate::force_law::Kernel;
+use crate::obstacle::Obstacle;
+use crate::particle_types::{ParticleTypes, DEFAULT_MASS, DEFAULT_RADIUS};
+use crate::reaction::Reaction;
+use crate::thermostat::Thermostat;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Population {
     pub particle_types: usize,
     pub particles: usize,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Seed {
     pub attract_mean: f32,
     pub attract_std: f32,
@@ -15,13 +28,244 @@ pub struct Seed {
     pub max_r_lower: f32,
     pub max_r_upper: f32,
     pub friction: f32,
-    pub flat_force: bool,
+    #[serde(default)]
+    pub kernel: Kernel,
+    // If not empty, every pair of types uses a kernel drawn from this list
+    // instead of `kernel`.
+    #[serde(default)]
+    pub kernels: Vec<Kernel>,
+    // Every type draws its mass and radius uniformly from these ranges, and
+    // its friction from `friction` plus or minus `friction_spread`.
+    #[serde(default = "default_mass")]
+    pub mass_lower: f32,
+    #[serde(default = "default_mass")]
+    pub mass_upper: f32,
+    #[serde(default = "default_radius")]
+    pub radius_lower: f32,
+    #[serde(default = "default_radius")]
+    pub radius_upper: f32,
+    #[serde(default)]
+    pub friction_spread: f32,
+    // Strength of the random motion of every type, see
+    // `Universe::set_type_temperature`.
+    #[serde(default)]
+    pub temperature: f32,
+    #[serde(default)]
+    pub thermostat: Thermostat,
+    // Files written before the force kernel was selectable have this flag
+    // instead of a `kernel`, see `fill_defaults`.
+    #[serde(default, skip_serializing)]
+    pub flat_force: Option<bool>,
+}
+
+impl Seed {
+    // Turns the legacy `flat_force` flag into the kernel it stood for, unless
+    // the file also picked another kernel.
+    pub fn fill_defaults(&mut self) {
+        if self.flat_force.take() == Some(true) && self.kernel == Kernel::default() {
+            self.kernel = Kernel::Flat;
+        }
+    }
+}
+
+fn default_mass() -> f32 {
+    DEFAULT_MASS
+}
+
+fn default_radius() -> f32 {
+    DEFAULT_RADIUS
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Preset {
     pub population: Population,
     pub seed: Seed,
+    #[serde(default)]
+    pub fields: Vec<ForceField>,
+    #[serde(default)]
+    pub boundary: Boundary,
+    #[serde(default)]
+    pub obstacles: Vec<Obstacle>,
+    #[serde(default)]
+    pub reactions: Vec<Reaction>,
+    #[serde(default)]
+    pub ecology: Option<Ecology>,
+    #[serde(default)]
+    pub bonds: Vec<BondRule>,
+    // Bonds per particle of each type, no limit if empty
+    #[serde(default)]
+    pub max_bonds: Vec<usize>,
+}
+
+// The exact interaction matrices and colors of a world, rather than the
+// distribution in `Seed` they were generated from. Loading one only randomizes
+// the particles.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConcretePreset {
+    pub particles: usize,
+    pub types: ParticleTypes,
+    #[serde(default)]
+    pub fields: Vec<ForceField>,
+    #[serde(default)]
+    pub boundary: Boundary,
+    #[serde(default)]
+    pub obstacles: Vec<Obstacle>,
+    #[serde(default)]
+    pub ecology: Option<Ecology>,
+    #[serde(default)]
+    pub thermostat: Thermostat,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidPreset(String);
+
+impl InvalidPreset {
+    pub fn new<S: Into<String>>(message: S) -> Self {
+        Self(message.into())
+    }
+}
+
+impl fmt::Display for InvalidPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for InvalidPreset {}
+
+fn check_finite(name: &str, value: f32) -> Result<(), InvalidPreset> {
+    if value.is_finite() {
+        Ok(())
+    } else {
+        Err(InvalidPreset(format!("{} must be a finite number", name)))
+    }
+}
+
+fn check_non_negative(name: &str, value: f32) -> Result<(), InvalidPreset> {
+    check_finite(name, value)?;
+
+    if value < 0.0 {
+        return Err(InvalidPreset(format!(
+            "{} ({}) must not be negative",
+            name, value
+        )));
+    }
+
+    Ok(())
+}
+
+fn check_range(
+    lower_name: &str,
+    lower: f32,
+    upper_name: &str,
+    upper: f32,
+) -> Result<(), InvalidPreset> {
+    check_non_negative(lower_name, lower)?;
+    check_non_negative(upper_name, upper)?;
+
+    if upper < lower {
+        return Err(InvalidPreset(format!(
+            "{} ({}) must not be less than {} ({})",
+            upper_name, upper, lower_name, lower
+        )));
+    }
+
+    Ok(())
+}
+
+fn check_friction(friction: f32) -> Result<(), InvalidPreset> {
+    check_non_negative("friction", friction)?;
+
+    if friction > 1.0 {
+        return Err(InvalidPreset(format!(
+            "friction ({}) must not be greater than 1",
+            friction
+        )));
+    }
+
+    Ok(())
+}
+
+impl Preset {
+    pub fn validate(&self) -> Result<(), InvalidPreset> {
+        if self.population.particle_types == 0 {
+            return Err(InvalidPreset(
+                "particle_types must be at least 1".to_owned(),
+            ));
+        }
+
+        let seed = &self.seed;
+        check_finite("attract_mean", seed.attract_mean)?;
+        check_non_negative("attract_std", seed.attract_std)?;
+        check_range(
+            "min_r_lower",
+            seed.min_r_lower,
+            "min_r_upper",
+            seed.min_r_upper,
+        )?;
+        check_range(
+            "max_r_lower",
+            seed.max_r_lower,
+            "max_r_upper",
+            seed.max_r_upper,
+        )?;
+        check_friction(seed.friction)?;
+        check_non_negative("friction_spread", seed.friction_spread)?;
+        check_non_negative("temperature", seed.temperature)?;
+        if let Thermostat::Rescale { kinetic_energy } = seed.thermostat {
+            check_non_negative("thermostat kinetic_energy", kinetic_energy)?;
+        }
+        check_range("mass_lower", seed.mass_lower, "mass_upper", seed.mass_upper)?;
+        if seed.mass_lower == 0.0 {
+            return Err(InvalidPreset(
+                "mass_lower must be greater than 0".to_owned(),
+            ));
+        }
+        check_range(
+            "radius_lower",
+            seed.radius_lower,
+            "radius_upper",
+            seed.radius_upper,
+        )?;
+
+        self.fields.iter().try_for_each(ForceField::validate)?;
+        self.obstacles.iter().try_for_each(Obstacle::validate)?;
+        self.reactions
+            .iter()
+            .try_for_each(|reaction| reaction.validate(self.population.particle_types))?;
+        self.bonds
+            .iter()
+            .try_for_each(|bond| bond.validate(self.population.particle_types))?;
+
+        if !self.max_bonds.is_empty() && self.max_bonds.len() != self.population.particle_types {
+            return Err(InvalidPreset(format!(
+                "max_bonds must have {} entries, found {}",
+                self.population.particle_types,
+                self.max_bonds.len()
+            )));
+        }
+
+        match &self.ecology {
+            Some(ecology) => ecology.validate(self.population.particle_types),
+            None => Ok(()),
+        }
+    }
+}
+
+impl ConcretePreset {
+    pub fn validate(&self) -> Result<(), InvalidPreset> {
+        self.types.validate()?;
+        if let Thermostat::Rescale { kinetic_energy } = self.thermostat {
+            check_non_negative("thermostat kinetic_energy", kinetic_energy)?;
+        }
+        self.fields.iter().try_for_each(ForceField::validate)?;
+        self.obstacles.iter().try_for_each(Obstacle::validate)?;
+
+        match &self.ecology {
+            Some(ecology) => ecology.validate(self.types.size()),
+            None => Ok(()),
+        }
+    }
 }
 
 pub static PRESETS: phf::OrderedMap<&'static str, Preset> = phf_ordered_map! {
@@ -38,8 +282,24 @@ pub static PRESETS: phf::OrderedMap<&'static str, Preset> = phf_ordered_map! {
             max_r_lower: 20.0,
             max_r_upper: 70.0,
             friction: 0.05,
-            flat_force: false
+            kernel: Kernel::Triangle,
+            kernels: Vec::new(),
+            mass_lower: DEFAULT_MASS,
+            mass_upper: DEFAULT_MASS,
+            radius_lower: DEFAULT_RADIUS,
+            radius_upper: DEFAULT_RADIUS,
+            friction_spread: 0.0,
+            temperature: 0.0,
+            thermostat: Thermostat::Off,
+            flat_force: None
         },
+        fields: Vec::new(),
+        boundary: Boundary::Reflect,
+        obstacles: Vec::new(),
+        reactions: Vec::new(),
+        ecology: None,
+        bonds: Vec::new(),
+        max_bonds: Vec::new(),
     },
     "Chaos" => Preset {
         population: Population {
@@ -54,8 +314,24 @@ pub static PRESETS: phf::OrderedMap<&'static str, Preset> = phf_ordered_map! {
             max_r_lower: 30.0,
             max_r_upper: 100.0,
             friction: 0.01,
-            flat_force: false
+            kernel: Kernel::Triangle,
+            kernels: Vec::new(),
+            mass_lower: DEFAULT_MASS,
+            mass_upper: DEFAULT_MASS,
+            radius_lower: DEFAULT_RADIUS,
+            radius_upper: DEFAULT_RADIUS,
+            friction_spread: 0.0,
+            temperature: 0.0,
+            thermostat: Thermostat::Off,
+            flat_force: None
         },
+        fields: Vec::new(),
+        boundary: Boundary::Reflect,
+        obstacles: Vec::new(),
+        reactions: Vec::new(),
+        ecology: None,
+        bonds: Vec::new(),
+        max_bonds: Vec::new(),
     },
     "Diversity" => Preset {
         population: Population {
@@ -70,8 +346,24 @@ pub static PRESETS: phf::OrderedMap<&'static str, Preset> = phf_ordered_map! {
             max_r_lower: 10.0,
             max_r_upper: 60.0,
             friction: 0.05,
-            flat_force: true
+            kernel: Kernel::Flat,
+            kernels: Vec::new(),
+            mass_lower: DEFAULT_MASS,
+            mass_upper: DEFAULT_MASS,
+            radius_lower: DEFAULT_RADIUS,
+            radius_upper: DEFAULT_RADIUS,
+            friction_spread: 0.0,
+            temperature: 0.0,
+            thermostat: Thermostat::Off,
+            flat_force: None
         },
+        fields: Vec::new(),
+        boundary: Boundary::Reflect,
+        obstacles: Vec::new(),
+        reactions: Vec::new(),
+        ecology: None,
+        bonds: Vec::new(),
+        max_bonds: Vec::new(),
     },
     "Frictionless" => Preset {
         population: Population {
@@ -86,8 +378,24 @@ pub static PRESETS: phf::OrderedMap<&'static str, Preset> = phf_ordered_map! {
             max_r_lower: 10.0,
             max_r_upper: 60.0,
             friction: 0.0,
-            flat_force: true
+            kernel: Kernel::Flat,
+            kernels: Vec::new(),
+            mass_lower: DEFAULT_MASS,
+            mass_upper: DEFAULT_MASS,
+            radius_lower: DEFAULT_RADIUS,
+            radius_upper: DEFAULT_RADIUS,
+            friction_spread: 0.0,
+            temperature: 0.0,
+            thermostat: Thermostat::Off,
+            flat_force: None
         },
+        fields: Vec::new(),
+        boundary: Boundary::Reflect,
+        obstacles: Vec::new(),
+        reactions: Vec::new(),
+        ecology: None,
+        bonds: Vec::new(),
+        max_bonds: Vec::new(),
     },
     "Gliders" => Preset {
         population: Population {
@@ -102,8 +410,24 @@ pub static PRESETS: phf::OrderedMap<&'static str, Preset> = phf_ordered_map! {
             max_r_lower: 10.0,
             max_r_upper: 50.0,
             friction: 0.1,
-            flat_force: true
+            kernel: Kernel::Flat,
+            kernels: Vec::new(),
+            mass_lower: DEFAULT_MASS,
+            mass_upper: DEFAULT_MASS,
+            radius_lower: DEFAULT_RADIUS,
+            radius_upper: DEFAULT_RADIUS,
+            friction_spread: 0.0,
+            temperature: 0.0,
+            thermostat: Thermostat::Off,
+            flat_force: None
         },
+        fields: Vec::new(),
+        boundary: Boundary::Reflect,
+        obstacles: Vec::new(),
+        reactions: Vec::new(),
+        ecology: None,
+        bonds: Vec::new(),
+        max_bonds: Vec::new(),
     },
     "Homogeneity" => Preset {
         population: Population {
@@ -118,8 +442,24 @@ pub static PRESETS: phf::OrderedMap<&'static str, Preset> = phf_ordered_map! {
             max_r_lower: 10.0,
             max_r_upper: 80.0,
             friction: 0.05,
-            flat_force: true
+            kernel: Kernel::Flat,
+            kernels: Vec::new(),
+            mass_lower: DEFAULT_MASS,
+            mass_upper: DEFAULT_MASS,
+            radius_lower: DEFAULT_RADIUS,
+            radius_upper: DEFAULT_RADIUS,
+            friction_spread: 0.0,
+            temperature: 0.0,
+            thermostat: Thermostat::Off,
+            flat_force: None
         },
+        fields: Vec::new(),
+        boundary: Boundary::Reflect,
+        obstacles: Vec::new(),
+        reactions: Vec::new(),
+        ecology: None,
+        bonds: Vec::new(),
+        max_bonds: Vec::new(),
     },
     "Large Clusters" => Preset {
         population: Population {
@@ -134,8 +474,24 @@ pub static PRESETS: phf::OrderedMap<&'static str, Preset> = phf_ordered_map! {
             max_r_lower: 30.0,
             max_r_upper: 100.0,
             friction: 0.2,
-            flat_force: false
+            kernel: Kernel::Triangle,
+            kernels: Vec::new(),
+            mass_lower: DEFAULT_MASS,
+            mass_upper: DEFAULT_MASS,
+            radius_lower: DEFAULT_RADIUS,
+            radius_upper: DEFAULT_RADIUS,
+            friction_spread: 0.0,
+            temperature: 0.0,
+            thermostat: Thermostat::Off,
+            flat_force: None
         },
+        fields: Vec::new(),
+        boundary: Boundary::Reflect,
+        obstacles: Vec::new(),
+        reactions: Vec::new(),
+        ecology: None,
+        bonds: Vec::new(),
+        max_bonds: Vec::new(),
     },
     "Medium Clusters" => Preset {
         population: Population {
@@ -150,8 +506,24 @@ pub static PRESETS: phf::OrderedMap<&'static str, Preset> = phf_ordered_map! {
             max_r_lower: 20.0,
             max_r_upper: 50.0,
             friction: 0.05,
-            flat_force: false
+            kernel: Kernel::Triangle,
+            kernels: Vec::new(),
+            mass_lower: DEFAULT_MASS,
+            mass_upper: DEFAULT_MASS,
+            radius_lower: DEFAULT_RADIUS,
+            radius_upper: DEFAULT_RADIUS,
+            friction_spread: 0.0,
+            temperature: 0.0,
+            thermostat: Thermostat::Off,
+            flat_force: None
         },
+        fields: Vec::new(),
+        boundary: Boundary::Reflect,
+        obstacles: Vec::new(),
+        reactions: Vec::new(),
+        ecology: None,
+        bonds: Vec::new(),
+        max_bonds: Vec::new(),
     },
     "Quiescence" => Preset {
         population: Population {
@@ -166,8 +538,24 @@ pub static PRESETS: phf::OrderedMap<&'static str, Preset> = phf_ordered_map! {
             max_r_lower: 20.0,
             max_r_upper: 60.0,
             friction: 0.2,
-            flat_force: false
+            kernel: Kernel::Triangle,
+            kernels: Vec::new(),
+            mass_lower: DEFAULT_MASS,
+            mass_upper: DEFAULT_MASS,
+            radius_lower: DEFAULT_RADIUS,
+            radius_upper: DEFAULT_RADIUS,
+            friction_spread: 0.0,
+            temperature: 0.0,
+            thermostat: Thermostat::Off,
+            flat_force: None
         },
+        fields: Vec::new(),
+        boundary: Boundary::Reflect,
+        obstacles: Vec::new(),
+        reactions: Vec::new(),
+        ecology: None,
+        bonds: Vec::new(),
+        max_bonds: Vec::new(),
     },
     "Small Clusters" => Preset {
         population: Population {
@@ -182,8 +570,24 @@ pub static PRESETS: phf::OrderedMap<&'static str, Preset> = phf_ordered_map! {
             max_r_lower: 20.0,
             max_r_upper: 50.0,
             friction: 0.01,
-            flat_force: false
+            kernel: Kernel::Triangle,
+            kernels: Vec::new(),
+            mass_lower: DEFAULT_MASS,
+            mass_upper: DEFAULT_MASS,
+            radius_lower: DEFAULT_RADIUS,
+            radius_upper: DEFAULT_RADIUS,
+            friction_spread: 0.0,
+            temperature: 0.0,
+            thermostat: Thermostat::Off,
+            flat_force: None
         },
+        fields: Vec::new(),
+        boundary: Boundary::Reflect,
+        obstacles: Vec::new(),
+        reactions: Vec::new(),
+        ecology: None,
+        bonds: Vec::new(),
+        max_bonds: Vec::new(),
     },
 };
 
@@ -0,0 +1,313 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::force_field::{FieldImageError, ForceField};
+use crate::obstacle::Obstacle;
+use crate::preset::{ConcretePreset, InvalidPreset, Preset, PRESETS};
+use crate::universe::Universe;
+
+#[derive(Debug)]
+pub enum PresetError {
+    Io(io::Error),
+    Toml(toml::de::Error),
+    Json(serde_json::Error),
+    UnknownFormat,
+    Serialize(serde_json::Error),
+    Invalid(InvalidPreset),
+    Image(FieldImageError),
+}
+
+impl fmt::Display for PresetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PresetError::Io(e) => write!(f, "{}", e),
+            PresetError::Toml(e) => write!(f, "invalid TOML: {}", e),
+            PresetError::Json(e) => write!(f, "invalid JSON: {}", e),
+            PresetError::UnknownFormat => write!(f, "expected a .toml or .json file"),
+            PresetError::Serialize(e) => write!(f, "failed to serialize preset: {}", e),
+            PresetError::Invalid(e) => write!(f, "invalid preset: {}", e),
+            PresetError::Image(e) => write!(f, "failed to load field image {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PresetError {}
+
+impl From<io::Error> for PresetError {
+    fn from(e: io::Error) -> Self {
+        PresetError::Io(e)
+    }
+}
+
+impl From<FieldImageError> for PresetError {
+    fn from(e: FieldImageError) -> Self {
+        PresetError::Image(e)
+    }
+}
+
+impl From<InvalidPreset> for PresetError {
+    fn from(e: InvalidPreset) -> Self {
+        PresetError::Invalid(e)
+    }
+}
+
+#[derive(Debug)]
+pub struct PresetFileError {
+    pub path: PathBuf,
+    pub error: PresetError,
+}
+
+impl fmt::Display for PresetFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.error)
+    }
+}
+
+impl std::error::Error for PresetFileError {}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+}
+
+#[derive(Debug, Clone)]
+pub enum LibraryPreset {
+    // Generates a new random world from a distribution on every load
+    Seeded(Preset),
+    // Reuses the exact interaction matrices of an exported world
+    Concrete(ConcretePreset),
+}
+
+impl LibraryPreset {
+    pub fn validate(&self) -> Result<(), InvalidPreset> {
+        match self {
+            LibraryPreset::Seeded(preset) => preset.validate(),
+            LibraryPreset::Concrete(preset) => preset.validate(),
+        }
+    }
+
+    fn fields_mut(&mut self) -> &mut [ForceField] {
+        match self {
+            LibraryPreset::Seeded(preset) => &mut preset.fields,
+            LibraryPreset::Concrete(preset) => &mut preset.fields,
+        }
+    }
+
+    fn obstacles_mut(&mut self) -> &mut [Obstacle] {
+        match self {
+            LibraryPreset::Seeded(preset) => &mut preset.obstacles,
+            LibraryPreset::Concrete(preset) => &mut preset.obstacles,
+        }
+    }
+
+    pub fn load_into(&self, universe: &mut Universe) {
+        match self {
+            LibraryPreset::Seeded(preset) => universe.load_preset(preset),
+            LibraryPreset::Concrete(preset) => universe.load_concrete_preset(preset),
+        }
+    }
+}
+
+// Reads and validates a single preset from a `.toml` or `.json` file. Files
+// with a `types` table are concrete presets, anything else a seeded one.
+pub fn load_preset_file<P: AsRef<Path>>(path: P) -> Result<LibraryPreset, PresetError> {
+    let path = path.as_ref();
+
+    let mut preset = match extension(path).as_deref() {
+        Some("toml") => {
+            // `toml::Value::try_into` cannot read enum variants with fields,
+            // such as force fields, so the text is only parsed into a `Value`
+            // to tell both kinds of presets apart
+            let text = fs::read_to_string(path)?;
+            let value: toml::Value = toml::from_str(&text).map_err(PresetError::Toml)?;
+            if value.get("types").is_some() {
+                LibraryPreset::Concrete(toml::from_str(&text).map_err(PresetError::Toml)?)
+            } else {
+                LibraryPreset::Seeded(toml::from_str(&text).map_err(PresetError::Toml)?)
+            }
+        }
+        Some("json") => {
+            let value: serde_json::Value =
+                serde_json::from_slice(&fs::read(path)?).map_err(PresetError::Json)?;
+            if value.get("types").is_some() {
+                LibraryPreset::Concrete(serde_json::from_value(value).map_err(PresetError::Json)?)
+            } else {
+                LibraryPreset::Seeded(serde_json::from_value(value).map_err(PresetError::Json)?)
+            }
+        }
+        _ => return Err(PresetError::UnknownFormat),
+    };
+
+    match &mut preset {
+        LibraryPreset::Seeded(preset) => preset.seed.fill_defaults(),
+        LibraryPreset::Concrete(preset) => preset.types.fill_defaults(),
+    }
+    preset.validate()?;
+
+    // Field images and masks are relative to the preset file
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    for field in preset.fields_mut() {
+        field.load_image(base)?;
+    }
+    for obstacle in preset.obstacles_mut() {
+        obstacle.load_image(base)?;
+    }
+
+    Ok(preset)
+}
+
+// Concrete presets are always written as JSON.
+pub fn save_concrete_preset<P: AsRef<Path>>(
+    path: P,
+    preset: &ConcretePreset,
+) -> Result<(), PresetError> {
+    let bytes = serde_json::to_vec_pretty(preset).map_err(PresetError::Serialize)?;
+    fs::write(path, bytes)?;
+    Ok(())
+}
+
+// Built-in presets followed by presets loaded from files. A file named after a
+// built-in preset replaces it in place.
+#[derive(Debug, Clone)]
+pub struct PresetLibrary {
+    presets: Vec<(String, LibraryPreset)>,
+}
+
+impl PresetLibrary {
+    pub fn builtin() -> Self {
+        Self {
+            presets: PRESETS
+                .entries()
+                .map(|(name, preset)| (name.to_string(), LibraryPreset::Seeded(preset.clone())))
+                .collect(),
+        }
+    }
+
+    pub fn insert(&mut self, name: String, preset: LibraryPreset) {
+        match self.position(&name) {
+            Some(index) => self.presets[index].1 = preset,
+            None => self.presets.push((name, preset)),
+        }
+    }
+
+    // Loads every `.toml` and `.json` file in `dir`, in file name order, named
+    // after the file stem. A missing directory is not an error. Files that
+    // fail to load are skipped and reported.
+    pub fn load_dir<P: AsRef<Path>>(&mut self, dir: P) -> Vec<PresetFileError> {
+        let dir = dir.as_ref();
+
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Vec::new(),
+            Err(e) => {
+                return vec![PresetFileError {
+                    path: dir.to_path_buf(),
+                    error: PresetError::Io(e),
+                }]
+            }
+        };
+
+        let mut paths = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| matches!(extension(path).as_deref(), Some("toml" | "json")))
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        let mut errors = Vec::new();
+
+        for path in paths {
+            let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(name) => name.to_owned(),
+                None => continue,
+            };
+
+            match load_preset_file(&path) {
+                Ok(preset) => self.insert(name, preset),
+                Err(error) => errors.push(PresetFileError { path, error }),
+            }
+        }
+
+        errors
+    }
+
+    pub fn len(&self) -> usize {
+        self.presets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.presets.is_empty()
+    }
+
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.presets.iter().position(|(key, _)| key == name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LibraryPreset> {
+        self.position(name).map(|index| &self.presets[index].1)
+    }
+
+    pub fn get_index(&self, index: usize) -> Option<(&str, &LibraryPreset)> {
+        self.presets
+            .get(index)
+            .map(|(name, preset)| (name.as_str(), preset))
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.presets.iter().map(|(name, _)| name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::force_law::Kernel;
+
+    #[test]
+    fn loads_legacy_flat_force_with_fields_and_obstacles() {
+        let dir = std::env::temp_dir().join(format!("particle-life-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Legacy.toml");
+        fs::write(
+            &path,
+            r#"
+obstacles = [
+    { circle = { x = 0.5, y = 0.5, radius = 40.0 } },
+]
+
+[population]
+particle_types = 3
+particles = 100
+
+[seed]
+attract_mean = 0.0
+attract_std = 0.05
+min_r_lower = 0.0
+min_r_upper = 20.0
+max_r_lower = 20.0
+max_r_upper = 70.0
+friction = 0.05
+flat_force = true
+
+[[fields]]
+kind = { gravity = { x = 0.0, y = 0.01 } }
+"#,
+        )
+        .unwrap();
+
+        let preset = load_preset_file(&path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        match preset.unwrap() {
+            LibraryPreset::Seeded(preset) => {
+                assert_eq!(preset.seed.kernel, Kernel::Flat);
+                assert_eq!(preset.seed.flat_force, None);
+                assert_eq!(preset.fields.len(), 1);
+                assert_eq!(preset.obstacles.len(), 1);
+            }
+            LibraryPreset::Concrete(_) => panic!("expected a seeded preset"),
+        }
+    }
+}
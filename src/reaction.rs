@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+use crate::preset::InvalidPreset;
+
+// A particle of type `reactant` within `radius` of a particle of type
+// `catalyst` turns into `product` with `probability` per step.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Reaction {
+    pub reactant: usize,
+    pub catalyst: usize,
+    pub product: usize,
+    pub radius: f32,
+    pub probability: f32,
+}
+
+impl Reaction {
+    pub fn new(
+        reactant: usize,
+        catalyst: usize,
+        product: usize,
+        radius: f32,
+        probability: f32,
+    ) -> Self {
+        Self {
+            reactant,
+            catalyst,
+            product,
+            radius,
+            probability,
+        }
+    }
+
+    // Whether all types are among the first `types`.
+    pub fn fits(&self, types: usize) -> bool {
+        self.reactant < types && self.catalyst < types && self.product < types
+    }
+
+    pub fn validate(&self, types: usize) -> Result<(), InvalidPreset> {
+        if !self.fits(types) {
+            return Err(InvalidPreset::new(format!(
+                "reaction {} + {} -> {} refers to a type beyond the {} types",
+                self.reactant, self.catalyst, self.product, types
+            )));
+        }
+
+        if !self.radius.is_finite() || self.radius < 0.0 {
+            return Err(InvalidPreset::new(format!(
+                "reaction radius ({}) must be a finite, non-negative number",
+                self.radius
+            )));
+        }
+
+        if !(0.0..=1.0).contains(&self.probability) {
+            return Err(InvalidPreset::new(format!(
+                "reaction probability ({}) must be between 0 and 1",
+                self.probability
+            )));
+        }
+
+        Ok(())
+    }
+}
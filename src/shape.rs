@@ -0,0 +1,103 @@
+// Circle in world coordinates, e.g. the area under the mouse brush.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Circle {
+    pub x: f64,
+    pub y: f64,
+    pub radius: f64,
+}
+
+impl Circle {
+    pub fn new(x: f64, y: f64, radius: f64) -> Self {
+        Self { x, y, radius }
+    }
+}
+
+// Line segment from `a` to `b` in world coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment {
+    pub a: (f64, f64),
+    pub b: (f64, f64),
+}
+
+impl Segment {
+    pub fn new(a: (f64, f64), b: (f64, f64)) -> Self {
+        Self { a, b }
+    }
+
+    pub fn closest_point(&self, x: f64, y: f64) -> (f64, f64) {
+        let ex = self.b.0 - self.a.0;
+        let ey = self.b.1 - self.a.1;
+        let len2 = ex * ex + ey * ey;
+
+        let t = if len2 > 0.0 {
+            (((x - self.a.0) * ex + (y - self.a.1) * ey) / len2).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        (self.a.0 + t * ex, self.a.1 + t * ey)
+    }
+
+    // Unit normal pointing to the side of (x, y).
+    pub fn normal_towards(&self, x: f64, y: f64) -> (f64, f64) {
+        let ex = self.b.0 - self.a.0;
+        let ey = self.b.1 - self.a.1;
+        let len = (ex * ex + ey * ey).sqrt();
+        if len == 0.0 {
+            return (1.0, 0.0);
+        }
+
+        let (nx, ny) = (-ey / len, ex / len);
+        if (x - self.a.0) * nx + (y - self.a.1) * ny < 0.0 {
+            (-nx, -ny)
+        } else {
+            (nx, ny)
+        }
+    }
+
+    // Whether both segments cross, not counting segments that only touch.
+    pub fn crosses(&self, other: &Segment) -> bool {
+        let orientation = |p: (f64, f64), q: (f64, f64), r: (f64, f64)| {
+            (q.0 - p.0) * (r.1 - p.1) - (q.1 - p.1) * (r.0 - p.0)
+        };
+
+        let d1 = orientation(self.a, self.b, other.a);
+        let d2 = orientation(self.a, self.b, other.b);
+        let d3 = orientation(other.a, other.b, self.a);
+        let d4 = orientation(other.a, other.b, self.b);
+
+        d1 * d2 < 0.0 && d3 * d4 < 0.0
+    }
+}
+
+// Closed polygon in world coordinates, the last point connecting back to the
+// first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    pub points: Vec<(f64, f64)>,
+}
+
+impl Polygon {
+    pub fn new(points: Vec<(f64, f64)>) -> Self {
+        Self { points }
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = Segment> + '_ {
+        let n = self.points.len();
+        (0..n).map(move |i| Segment::new(self.points[i], self.points[(i + 1) % n]))
+    }
+
+    // Even-odd rule, so self-intersecting polygons have holes.
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        let mut inside = false;
+
+        for edge in self.edges() {
+            let ((x1, y1), (x2, y2)) = (edge.a, edge.b);
+            if (y1 > y) != (y2 > y) && x < x1 + (y - y1) / (y2 - y1) * (x2 - x1) {
+                inside = !inside;
+            }
+        }
+
+        inside
+    }
+}
@@ -0,0 +1,224 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::bond::Bond;
+use crate::boundary::Boundary;
+use crate::ecology::Ecology;
+use crate::force_field::{FieldImageError, ForceField};
+use crate::force_law::Kernel;
+use crate::forces::ForceMode;
+use crate::integrator::Integrator;
+use crate::obstacle::Obstacle;
+use crate::particle::Particle;
+use crate::particle_types::ParticleTypes;
+use crate::preset::InvalidPreset;
+use crate::thermostat::Thermostat;
+
+// Bump whenever the layout of `Snapshot` changes.
+pub const SNAPSHOT_VERSION: u32 = 12;
+
+const BINARY_MAGIC: &[u8; 4] = b"PLSS";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    Binary,
+    Json,
+}
+
+impl SnapshotFormat {
+    // `.json` files are stored as JSON, anything else as binary.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => SnapshotFormat::Json,
+            _ => SnapshotFormat::Binary,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    Binary(bincode::Error),
+    Json(serde_json::Error),
+    InvalidHeader,
+    UnsupportedVersion(u32),
+    Image(FieldImageError),
+    Invalid(InvalidPreset),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "{}", e),
+            SnapshotError::Binary(e) => write!(f, "invalid binary snapshot: {}", e),
+            SnapshotError::Json(e) => write!(f, "invalid JSON snapshot: {}", e),
+            SnapshotError::InvalidHeader => write!(f, "not a snapshot file"),
+            SnapshotError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported snapshot version {} (expected {})",
+                version, SNAPSHOT_VERSION
+            ),
+            SnapshotError::Image(e) => write!(f, "failed to load field image {}", e),
+            SnapshotError::Invalid(e) => write!(f, "invalid snapshot: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+impl From<FieldImageError> for SnapshotError {
+    fn from(e: FieldImageError) -> Self {
+        SnapshotError::Image(e)
+    }
+}
+
+impl From<InvalidPreset> for SnapshotError {
+    fn from(e: InvalidPreset) -> Self {
+        SnapshotError::Invalid(e)
+    }
+}
+
+impl From<bincode::Error> for SnapshotError {
+    fn from(e: bincode::Error) -> Self {
+        SnapshotError::Binary(e)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(e: serde_json::Error) -> Self {
+        SnapshotError::Json(e)
+    }
+}
+
+// Full state of a `Universe`, enough to resume the simulation identically.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub width: f32,
+    pub height: f32,
+    pub types: ParticleTypes,
+    pub particles: Vec<Particle>,
+    pub attract_mean: f32,
+    pub attract_std: f32,
+    pub min_r_lower: f32,
+    pub min_r_upper: f32,
+    pub max_r_lower: f32,
+    pub max_r_upper: f32,
+    pub friction: f32,
+    pub kernel: Kernel,
+    pub kernels: Vec<Kernel>,
+    pub mass_lower: f32,
+    pub mass_upper: f32,
+    pub radius_lower: f32,
+    pub radius_upper: f32,
+    pub friction_spread: f32,
+    pub temperature: f32,
+    pub thermostat: Thermostat,
+    pub fields: Vec<ForceField>,
+    pub boundary: Boundary,
+    pub obstacles: Vec<Obstacle>,
+    pub ecology: Option<Ecology>,
+    pub bonds: Vec<Bond>,
+    pub integrator: Integrator,
+    pub force_mode: ForceMode,
+    pub rng_seed: u64,
+    pub rng: ChaCha8Rng,
+}
+
+impl Snapshot {
+    pub fn to_bytes(&self, format: SnapshotFormat) -> Result<Vec<u8>, SnapshotError> {
+        match format {
+            SnapshotFormat::Binary => {
+                let mut bytes = BINARY_MAGIC.to_vec();
+                bytes.extend_from_slice(&self.version.to_le_bytes());
+                bincode::serialize_into(&mut bytes, self)?;
+                Ok(bytes)
+            }
+            SnapshotFormat::Json => Ok(serde_json::to_vec_pretty(self)?),
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8], format: SnapshotFormat) -> Result<Self, SnapshotError> {
+        match format {
+            SnapshotFormat::Binary => {
+                if bytes.len() < 8 || &bytes[..4] != BINARY_MAGIC {
+                    return Err(SnapshotError::InvalidHeader);
+                }
+
+                let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+                if version != SNAPSHOT_VERSION {
+                    return Err(SnapshotError::UnsupportedVersion(version));
+                }
+
+                Ok(bincode::deserialize(&bytes[8..])?)
+            }
+            SnapshotFormat::Json => {
+                // Check the version before the layout, which may have changed
+                let value: serde_json::Value = serde_json::from_slice(bytes)?;
+                let version = value
+                    .get("version")
+                    .and_then(|version| version.as_u64())
+                    .ok_or(SnapshotError::InvalidHeader)?;
+                if version != SNAPSHOT_VERSION as u64 {
+                    return Err(SnapshotError::UnsupportedVersion(version as u32));
+                }
+
+                Ok(serde_json::from_value(value)?)
+            }
+        }
+    }
+
+    // Checks everything a `Universe` indexes with before it is restored.
+    pub fn validate(&self) -> Result<(), InvalidPreset> {
+        self.types.validate()?;
+
+        if let Some(p) = self
+            .particles
+            .iter()
+            .find(|p| p.particle_type >= self.types.size())
+        {
+            return Err(InvalidPreset::new(format!(
+                "particle type {} is beyond the {} types",
+                p.particle_type,
+                self.types.size()
+            )));
+        }
+
+        if let Some(bond) = self
+            .bonds
+            .iter()
+            .find(|bond| bond.a >= bond.b || bond.b >= self.particles.len())
+        {
+            return Err(InvalidPreset::new(format!(
+                "bond between particles {} and {} does not fit the {} particles",
+                bond.a,
+                bond.b,
+                self.particles.len()
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError> {
+        let bytes = self.to_bytes(SnapshotFormat::from_path(&path))?;
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SnapshotError> {
+        let bytes = fs::read(&path)?;
+        Self::from_bytes(&bytes, SnapshotFormat::from_path(&path))
+    }
+}
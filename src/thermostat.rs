@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+use crate::particle::Particle;
+use crate::particle_types::ParticleTypes;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(from = "ThermostatConfig", into = "ThermostatConfig")]
+pub enum Thermostat {
+    #[default]
+    Off,
+    // Rescales all velocities after every step so that the mean kinetic energy
+    // per particle stays at `kinetic_energy`.
+    Rescale {
+        kinetic_energy: f32,
+    },
+}
+
+// Stored as `{ mode = "rescale", kinetic_energy = 0.5 }`. A plain struct rather
+// than a tagged enum, which neither TOML nor bincode can read back.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct ThermostatConfig {
+    mode: ThermostatMode,
+    #[serde(default)]
+    kinetic_energy: f32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ThermostatMode {
+    Off,
+    Rescale,
+}
+
+impl From<ThermostatConfig> for Thermostat {
+    fn from(config: ThermostatConfig) -> Self {
+        match config.mode {
+            ThermostatMode::Off => Thermostat::Off,
+            ThermostatMode::Rescale => Thermostat::Rescale {
+                kinetic_energy: config.kinetic_energy,
+            },
+        }
+    }
+}
+
+impl From<Thermostat> for ThermostatConfig {
+    fn from(thermostat: Thermostat) -> Self {
+        match thermostat {
+            Thermostat::Off => ThermostatConfig {
+                mode: ThermostatMode::Off,
+                kinetic_energy: 0.0,
+            },
+            Thermostat::Rescale { kinetic_energy } => ThermostatConfig {
+                mode: ThermostatMode::Rescale,
+                kinetic_energy,
+            },
+        }
+    }
+}
+
+impl Thermostat {
+    pub fn apply(&self, particles: &mut [Particle], types: &ParticleTypes) {
+        let target = match self {
+            Thermostat::Off => return,
+            Thermostat::Rescale { kinetic_energy } => *kinetic_energy as f64,
+        };
+
+        let current = kinetic_energy(particles, types);
+
+        // A world at rest has no direction to scale up
+        if current <= 0.0 {
+            return;
+        }
+
+        let scale = (target * particles.len() as f64 / current).sqrt();
+        for p in particles.iter_mut() {
+            p.vx *= scale;
+            p.vy *= scale;
+        }
+    }
+}
+
+pub fn kinetic_energy(particles: &[Particle], types: &ParticleTypes) -> f64 {
+    particles
+        .iter()
+        .map(|p| {
+            let mass = *types.get_mass(p.particle_type).unwrap() as f64;
+            0.5 * mass * (p.vx * p.vx + p.vy * p.vy)
+        })
+        .sum()
+}
@@ -1,16 +1,35 @@
-use ::rand::prelude::ThreadRng;
-use macroquad::color::Color;
-use macroquad::prelude::*;
-use rand_distr::{Distribution, Normal, Uniform};
+use std::collections::{HashSet, VecDeque};
+use std::path::Path;
 
-use crate::hsv::HSV;
+use ::rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution, Normal, StandardNormal, Uniform};
+
+use crate::bond::{self, Bond, BondRule};
+use crate::boundary::Boundary;
+#[cfg(feature = "render")]
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::ecology::Ecology;
+use crate::force_field::{FieldKind, ForceField};
+use crate::force_law::Kernel;
+use crate::forces::{ForceParams, ForceSolver};
+use crate::grid::SpatialGrid;
+use crate::hsv::Hsv;
+use crate::integrator::{Integrator, IntegratorScratch};
+use crate::obstacle::Obstacle;
 use crate::particle::Particle;
-use crate::particle_types::ParticleTypes;
-use crate::preset::{Preset, Seed};
+use crate::particle_types::{ParticleTypes, DEFAULT_MASS, DEFAULT_RADIUS};
+use crate::preset::{ConcretePreset, Preset, Seed};
+use crate::reaction::Reaction;
+use crate::shape::Circle;
+use crate::snapshot::{Snapshot, SnapshotError, SNAPSHOT_VERSION};
+use crate::thermostat::{self, Thermostat};
+
+pub use crate::forces::{Backend, ForceMode, NeighborSearch};
 
-const RADIUS: f32 = 5.0;
-const DIAMETER: f32 = 2.0 * RADIUS;
-const R_SMOOTH: f64 = 2.0;
+// Number of steps whose populations are kept
+const POPULATION_HISTORY: usize = 10_000;
 
 #[derive(Debug)]
 pub struct Universe {
@@ -18,9 +37,6 @@ pub struct Universe {
     particles: Vec<Particle>,
     width: f32,
     height: f32,
-    center_x: f32,
-    center_y: f32,
-    pub zoom: f32,
     attract_mean: f32,
     attract_std: f32,
     min_r_lower: f32,
@@ -28,21 +44,47 @@ pub struct Universe {
     max_r_lower: f32,
     max_r_upper: f32,
     friction: f32,
-    flat_force: bool,
-    wrap: bool,
-    rng: ThreadRng,
+    kernel: Kernel,
+    kernels: Vec<Kernel>,
+    mass_lower: f32,
+    mass_upper: f32,
+    radius_lower: f32,
+    radius_upper: f32,
+    friction_spread: f32,
+    temperature: f32,
+    thermostat: Thermostat,
+    fields: Vec<ForceField>,
+    // Applied like `fields` while the user drags the mouse, never saved
+    brush: Option<ForceField>,
+    boundary: Boundary,
+    obstacles: Vec<Obstacle>,
+    // How often each reaction of the types happened since they were set
+    reaction_counts: Vec<u64>,
+    ecology: Option<Ecology>,
+    // Number of particles of every type after each step with an ecology
+    populations: VecDeque<Vec<usize>>,
+    // Springs between particles, see `ParticleTypes::set_bond`
+    bonds: Vec<Bond>,
+    // Neighbors within the range of reactions, feeding or bonding
+    contact_grid: SpatialGrid,
+    solver: ForceSolver,
+    integrator: Integrator,
+    integrator_scratch: IntegratorScratch,
+    // Positions before the last step, for interpolated rendering
+    previous_positions: Vec<(f64, f64)>,
+    rng_seed: u64,
+    rng: ChaCha8Rng,
 }
 
 impl Universe {
     pub fn new(width: f32, height: f32) -> Self {
+        let rng_seed = ::rand::random();
+
         Self {
             types: ParticleTypes::new(0),
-            particles: vec![Particle::default(); 0],
+            particles: Vec::new(),
             width,
             height,
-            center_x: width * 0.5,
-            center_y: height * 0.5,
-            zoom: 1.0,
             attract_mean: 0.0,
             attract_std: 0.0,
             min_r_lower: 0.0,
@@ -50,9 +92,30 @@ impl Universe {
             max_r_lower: 0.0,
             max_r_upper: 0.0,
             friction: 0.0,
-            flat_force: false,
-            wrap: false,
-            rng: ThreadRng::default(),
+            kernel: Kernel::default(),
+            kernels: Vec::new(),
+            mass_lower: DEFAULT_MASS,
+            mass_upper: DEFAULT_MASS,
+            radius_lower: DEFAULT_RADIUS,
+            radius_upper: DEFAULT_RADIUS,
+            friction_spread: 0.0,
+            temperature: 0.0,
+            thermostat: Thermostat::Off,
+            fields: Vec::new(),
+            brush: None,
+            boundary: Boundary::default(),
+            obstacles: Vec::new(),
+            reaction_counts: Vec::new(),
+            ecology: None,
+            populations: VecDeque::new(),
+            bonds: Vec::new(),
+            contact_grid: SpatialGrid::new(),
+            solver: ForceSolver::new(),
+            integrator: Integrator::default(),
+            integrator_scratch: IntegratorScratch::default(),
+            previous_positions: Vec::new(),
+            rng_seed,
+            rng: ChaCha8Rng::seed_from_u64(rng_seed),
         }
     }
 
@@ -64,14 +127,24 @@ impl Universe {
         self.max_r_lower = seed.max_r_lower;
         self.max_r_upper = seed.max_r_upper;
         self.friction = seed.friction;
-        self.flat_force = seed.flat_force;
+        self.kernel = seed.kernel;
+        self.kernels = seed.kernels.clone();
+        self.mass_lower = seed.mass_lower;
+        self.mass_upper = seed.mass_upper;
+        self.radius_lower = seed.radius_lower;
+        self.radius_upper = seed.radius_upper;
+        self.friction_spread = seed.friction_spread;
+        self.temperature = seed.temperature;
+        self.thermostat = seed.thermostat;
         self.set_random_types();
         self.set_random_particles();
     }
 
     pub fn set_population(&mut self, num_types: usize, num_particles: usize) {
         self.types.resize(num_types);
+        self.reset_reaction_counts();
         self.particles.resize(num_particles, Particle::default());
+        self.bonds.clear();
     }
 
     pub fn load_preset(&mut self, preset: &Preset) {
@@ -79,10 +152,323 @@ impl Universe {
             preset.population.particle_types,
             preset.population.particles,
         );
+        // Before the particles are created, which takes their energy from it
+        self.ecology = preset.ecology.clone();
+        self.populations.clear();
         self.reseed(&preset.seed);
+        self.fields = preset.fields.clone();
+        self.boundary = preset.boundary;
+        self.obstacles = preset.obstacles.clone();
+        self.set_reactions(preset.reactions.clone());
+        self.set_bond_rules(&preset.bonds, &preset.max_bonds);
+    }
+
+    pub fn load_concrete_preset(&mut self, preset: &ConcretePreset) {
+        self.types = preset.types.clone();
+        self.reset_reaction_counts();
+        self.particles.resize(preset.particles, Particle::default());
+        self.fields = preset.fields.clone();
+        self.boundary = preset.boundary;
+        self.obstacles = preset.obstacles.clone();
+        self.ecology = preset.ecology.clone();
+        self.populations.clear();
+        self.thermostat = preset.thermostat;
+
+        // The settings changed for all types at once start from the loaded
+        // types rather than the previous preset
+        let size = self.types.size();
+        self.temperature = (0..size)
+            .map(|i| *self.types.get_temperature(i).unwrap())
+            .fold(0.0, f32::max);
+        self.kernel = self.types.get_kernel(0, 0).copied().unwrap_or_default();
+        self.kernels.clear();
+
+        self.set_random_particles();
+    }
+
+    // Captures the current interaction matrices, see `ConcretePreset`.
+    pub fn concrete_preset(&self) -> ConcretePreset {
+        ConcretePreset {
+            particles: self.particles.len(),
+            types: self.types.clone(),
+            fields: self.fields.clone(),
+            boundary: self.boundary,
+            obstacles: self.obstacles.clone(),
+            ecology: self.ecology.clone(),
+            thermostat: self.thermostat,
+        }
+    }
+
+    // Restarts the random engine from `seed`, so that loading the same preset
+    // afterwards reproduces the same types and initial particles.
+    pub fn set_rng_seed(&mut self, seed: u64) {
+        self.rng_seed = seed;
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+    }
+
+    pub fn rng_seed(&self) -> u64 {
+        self.rng_seed
+    }
+
+    // Seeds the random engine from any other random number generator.
+    pub fn set_engine<R: RngCore>(&mut self, new_engine: R) {
+        let seed = ChaCha8Rng::from_rng(new_engine)
+            .expect("failed to seed engine")
+            .next_u64();
+        self.set_rng_seed(seed);
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            width: self.width,
+            height: self.height,
+            types: self.types.clone(),
+            particles: self.particles.clone(),
+            attract_mean: self.attract_mean,
+            attract_std: self.attract_std,
+            min_r_lower: self.min_r_lower,
+            min_r_upper: self.min_r_upper,
+            max_r_lower: self.max_r_lower,
+            max_r_upper: self.max_r_upper,
+            friction: self.friction,
+            kernel: self.kernel,
+            kernels: self.kernels.clone(),
+            mass_lower: self.mass_lower,
+            mass_upper: self.mass_upper,
+            radius_lower: self.radius_lower,
+            radius_upper: self.radius_upper,
+            friction_spread: self.friction_spread,
+            temperature: self.temperature,
+            thermostat: self.thermostat,
+            fields: self.fields.clone(),
+            boundary: self.boundary,
+            obstacles: self.obstacles.clone(),
+            ecology: self.ecology.clone(),
+            bonds: self.bonds.clone(),
+            integrator: self.integrator,
+            force_mode: self.solver.mode,
+            rng_seed: self.rng_seed,
+            rng: self.rng.clone(),
+        }
     }
 
-    // pub fn set_engine<R: RngCore>(&mut self, new_engine: R) {}
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.width = snapshot.width;
+        self.height = snapshot.height;
+        self.types = snapshot.types;
+        self.reset_reaction_counts();
+        self.particles = snapshot.particles;
+        self.attract_mean = snapshot.attract_mean;
+        self.attract_std = snapshot.attract_std;
+        self.min_r_lower = snapshot.min_r_lower;
+        self.min_r_upper = snapshot.min_r_upper;
+        self.max_r_lower = snapshot.max_r_lower;
+        self.max_r_upper = snapshot.max_r_upper;
+        self.friction = snapshot.friction;
+        self.kernel = snapshot.kernel;
+        self.kernels = snapshot.kernels;
+        self.mass_lower = snapshot.mass_lower;
+        self.mass_upper = snapshot.mass_upper;
+        self.radius_lower = snapshot.radius_lower;
+        self.radius_upper = snapshot.radius_upper;
+        self.friction_spread = snapshot.friction_spread;
+        self.temperature = snapshot.temperature;
+        self.thermostat = snapshot.thermostat;
+        self.fields = snapshot.fields;
+        self.boundary = snapshot.boundary;
+        self.obstacles = snapshot.obstacles;
+        self.ecology = snapshot.ecology;
+        self.populations.clear();
+        self.bonds = snapshot.bonds;
+        self.integrator = snapshot.integrator;
+        self.solver.mode = snapshot.force_mode;
+        self.rng_seed = snapshot.rng_seed;
+        self.rng = snapshot.rng;
+        self.previous_positions.clear();
+    }
+
+    // The format is picked from the extension, see `SnapshotFormat::from_path`.
+    pub fn save_snapshot<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError> {
+        self.snapshot().save(path)
+    }
+
+    pub fn load_snapshot<P: AsRef<Path>>(&mut self, path: P) -> Result<(), SnapshotError> {
+        let mut snapshot = Snapshot::load(path)?;
+        snapshot.validate()?;
+
+        // Snapshots only store the paths of field images and masks
+        for field in snapshot.fields.iter_mut() {
+            field.load_image(Path::new(""))?;
+        }
+        for obstacle in snapshot.obstacles.iter_mut() {
+            obstacle.load_image(Path::new(""))?;
+        }
+
+        self.restore(snapshot);
+        Ok(())
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    pub fn types(&self) -> &ParticleTypes {
+        &self.types
+    }
+
+    // Adds a particle, wrapping its position into the world if it wraps.
+    pub fn add_particle(&mut self, mut particle: Particle) {
+        assert!(
+            particle.particle_type < self.types.size(),
+            "no particle type {}",
+            particle.particle_type
+        );
+
+        if self.boundary.wraps() {
+            particle.x = particle.x.rem_euclid(self.width as f64);
+            particle.y = particle.y.rem_euclid(self.height as f64);
+        }
+
+        // Starts where it is when interpolating
+        if self.previous_positions.len() == self.particles.len() {
+            self.previous_positions.push((particle.x, particle.y));
+        }
+        self.particles.push(particle);
+    }
+
+    // Removes every particle inside `circle` and returns how many there were.
+    pub fn remove_particles_in(&mut self, circle: Circle) -> usize {
+        let count = self.particles.len();
+        let keep: Vec<bool> = self
+            .particles
+            .iter()
+            .map(|p| !self.contains(circle, p))
+            .collect();
+
+        self.retain_particles(&keep);
+        count - self.particles.len()
+    }
+
+    // Keeps the particles whose entry in `keep` is true, along with their
+    // previous positions and the bonds between them.
+    fn retain_particles(&mut self, keep: &[bool]) {
+        if !self.bonds.is_empty() {
+            // Index of every kept particle once the others are gone
+            let mut new_index = Vec::with_capacity(keep.len());
+            let mut next = 0;
+            for &kept in keep {
+                new_index.push(next);
+                if kept {
+                    next += 1;
+                }
+            }
+
+            self.bonds.retain(|bond| keep[bond.a] && keep[bond.b]);
+            for bond in self.bonds.iter_mut() {
+                bond.a = new_index[bond.a];
+                bond.b = new_index[bond.b];
+            }
+        }
+
+        if self.previous_positions.len() == self.particles.len() {
+            let mut index = 0;
+            self.previous_positions.retain(|_| {
+                index += 1;
+                keep[index - 1]
+            });
+        }
+
+        let mut index = 0;
+        self.particles.retain(|_| {
+            index += 1;
+            keep[index - 1]
+        });
+    }
+
+    // Changes every particle inside `circle` to `particle_type` and returns how
+    // many changed.
+    pub fn set_type_in(&mut self, circle: Circle, particle_type: usize) -> usize {
+        assert!(
+            particle_type < self.types.size(),
+            "no particle type {}",
+            particle_type
+        );
+
+        let mut count = 0;
+        for i in 0..self.particles.len() {
+            if self.particles[i].particle_type != particle_type
+                && self.contains(circle, &self.particles[i])
+            {
+                self.particles[i].particle_type = particle_type;
+                count += 1;
+            }
+        }
+
+        count
+    }
+
+    // Whether `p` is inside `circle`, measuring across the edges of a
+    // wrapping world.
+    fn contains(&self, circle: Circle, p: &Particle) -> bool {
+        let (dx, dy) = self.boundary.displacement(
+            p.x - circle.x,
+            p.y - circle.y,
+            self.width as f64,
+            self.height as f64,
+        );
+
+        dx * dx + dy * dy <= circle.radius * circle.radius
+    }
+
+    // Total kinetic energy, to measure how well an integrator conserves energy
+    // in a frictionless universe.
+    pub fn kinetic_energy(&self) -> f64 {
+        thermostat::kinetic_energy(&self.particles, &self.types)
+    }
+
+    // Total momentum, which stays constant in `ForceMode::Reciprocal` as long
+    // as there is no friction and the world wraps or is open.
+    pub fn momentum(&self) -> (f64, f64) {
+        self.particles.iter().fold((0.0, 0.0), |(px, py), p| {
+            let mass = *self.types.get_mass(p.particle_type).unwrap() as f64;
+            (px + mass * p.vx, py + mass * p.vy)
+        })
+    }
+
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+
+    // Mass-weighted mean position of the particles, or the center of the world
+    // if it is empty.
+    pub fn center_of_mass(&self) -> (f64, f64) {
+        let (mut x, mut y, mut total) = (0.0, 0.0, 0.0);
+        for p in &self.particles {
+            let mass = *self.types.get_mass(p.particle_type).unwrap() as f64;
+            x += mass * p.x;
+            y += mass * p.y;
+            total += mass;
+        }
+
+        if total > 0.0 {
+            (x / total, y / total)
+        } else {
+            (self.width as f64 * 0.5, self.height as f64 * 0.5)
+        }
+    }
+
+    pub fn boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    pub fn set_boundary(&mut self, boundary: Boundary) {
+        self.boundary = boundary;
+    }
 
     pub fn set_size(&mut self, width: f32, height: f32) {
         self.width = width;
@@ -93,6 +479,20 @@ impl Universe {
         let rand_attr = Normal::new(self.attract_mean, self.attract_std).unwrap();
         let rand_min_r = Uniform::new_inclusive(self.min_r_lower, self.min_r_upper);
         let rand_max_r = Uniform::new_inclusive(self.max_r_lower, self.max_r_upper);
+        let rand_kernel = Uniform::new(0, self.kernels.len().max(1));
+
+        let friction_lower = (self.friction - self.friction_spread).max(0.0);
+        let friction_upper = (self.friction + self.friction_spread).min(1.0);
+
+        for i in 0..self.types.size() {
+            let mass = sample_range(&mut self.rng, self.mass_lower, self.mass_upper);
+            let radius = sample_range(&mut self.rng, self.radius_lower, self.radius_upper);
+            let friction = sample_range(&mut self.rng, friction_lower, friction_upper);
+            self.types.set_mass(i, mass);
+            self.types.set_radius(i, radius);
+            self.types.set_friction(i, friction);
+            self.types.set_temperature(i, self.temperature);
+        }
 
         for i in 0..self.types.size() {
             self.types.set_color(
@@ -105,15 +505,18 @@ impl Universe {
             );
 
             for j in 0..self.types.size() {
+                // Particles of these types touch at this distance
+                let contact = self.types.get_radius(i).unwrap() + self.types.get_radius(j).unwrap();
+
                 if i == j {
                     self.types
                         .set_attract(i, j, -rand_attr.sample(&mut self.rng).abs());
-                    self.types.set_min_r(i, j, DIAMETER);
+                    self.types.set_min_r(i, j, contact);
                 } else {
                     self.types
                         .set_attract(i, j, rand_attr.sample(&mut self.rng));
                     self.types
-                        .set_min_r(i, j, rand_min_r.sample(&mut self.rng).max(DIAMETER))
+                        .set_min_r(i, j, rand_min_r.sample(&mut self.rng).max(contact))
                 }
 
                 self.types.set_max_r(
@@ -129,220 +532,817 @@ impl Universe {
                     .set_max_r(j, i, *self.types.get_max_r(i, j).unwrap());
                 self.types
                     .set_min_r(j, i, *self.types.get_min_r(i, j).unwrap());
+
+                let kernel = if self.kernels.is_empty() {
+                    self.kernel
+                } else {
+                    self.kernels[rand_kernel.sample(&mut self.rng)]
+                };
+                self.types.set_kernel(i, j, kernel);
             }
         }
     }
 
     pub fn set_random_particles(&mut self) {
-        let rand_type = Uniform::new(0, self.types.size() - 1);
+        let rand_type = Uniform::new(0, self.types.size());
         let rand_uni = Uniform::new(0.0, 1.0);
         let rand_norm = Normal::new(0.0, 1.0).unwrap();
+        let energy = self.initial_energy();
+
+        self.previous_positions.clear();
+        self.bonds.clear();
 
         // for i in 0..self.particles.len() {}
-        for mut p in self.particles.iter_mut() {
+        for p in self.particles.iter_mut() {
             p.particle_type = rand_type.sample(&mut self.rng);
             p.x = (rand_uni.sample(&mut self.rng) * 0.5 + 0.25) * self.width as f64;
             p.y = (rand_uni.sample(&mut self.rng) * 0.5 + 0.25) * self.height as f64;
 
             p.vx = rand_norm.sample(&mut self.rng) * 0.2;
             p.vy = rand_norm.sample(&mut self.rng) * 0.2;
+            p.energy = energy;
         }
     }
 
-    pub fn step(&mut self) {
-        let size = self.particles.len();
+    pub fn set_neighbor_search(&mut self, neighbor_search: NeighborSearch) {
+        self.solver.neighbor_search = neighbor_search;
+    }
 
-        // TODO: Rewrite in a rust-way?
-        for i in 0..size {
-            // Interactions
-            for j in 0..size {
-                // Cannot overlap
-                if i == j {
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.solver.backend = backend;
+    }
+
+    pub fn force_mode(&self) -> ForceMode {
+        self.solver.mode
+    }
+
+    pub fn set_force_mode(&mut self, mode: ForceMode) {
+        self.solver.mode = mode;
+    }
+
+    pub fn integrator(&self) -> Integrator {
+        self.integrator
+    }
+
+    pub fn set_integrator(&mut self, integrator: Integrator) {
+        self.integrator = integrator;
+    }
+
+    pub fn kernel(&self) -> Kernel {
+        self.kernel
+    }
+
+    // Uses `kernel` for every pair of types.
+    pub fn set_kernel(&mut self, kernel: Kernel) {
+        self.kernel = kernel;
+        self.kernels.clear();
+
+        for i in 0..self.types.size() {
+            for j in 0..self.types.size() {
+                self.types.set_kernel(i, j, kernel);
+            }
+        }
+    }
+
+    pub fn temperature(&self) -> f32 {
+        self.temperature
+    }
+
+    // Uses `temperature` for every type, see `set_type_temperature`.
+    pub fn set_temperature(&mut self, temperature: f32) {
+        self.temperature = temperature;
+
+        for i in 0..self.types.size() {
+            self.types.set_temperature(i, temperature);
+        }
+    }
+
+    // Every step adds Gaussian noise with a variance of
+    // `temperature * dt / mass` to each velocity component of the particles
+    // of this type.
+    pub fn set_type_temperature(&mut self, index: usize, temperature: f32) {
+        self.types.set_temperature(index, temperature);
+    }
+
+    pub fn force_fields(&self) -> &[ForceField] {
+        &self.fields
+    }
+
+    pub fn add_force_field(&mut self, field: ForceField) {
+        self.fields.push(field);
+    }
+
+    pub fn remove_force_field(&mut self, index: usize) -> ForceField {
+        self.fields.remove(index)
+    }
+
+    // Returns whether the field is enabled afterwards, or `None` if there is
+    // no field at `index`.
+    pub fn toggle_force_field(&mut self, index: usize) -> Option<bool> {
+        let field = self.fields.get_mut(index)?;
+        field.enabled = !field.enabled;
+        Some(field.enabled)
+    }
+
+    pub fn obstacles(&self) -> &[Obstacle] {
+        &self.obstacles
+    }
+
+    pub fn add_obstacle(&mut self, obstacle: Obstacle) {
+        self.obstacles.push(obstacle);
+    }
+
+    pub fn remove_obstacle(&mut self, index: usize) -> Obstacle {
+        self.obstacles.remove(index)
+    }
+
+    pub fn ecology(&self) -> Option<&Ecology> {
+        self.ecology.as_ref()
+    }
+
+    // Gives every particle the initial energy of the new ecology, and starts
+    // recording the populations anew.
+    pub fn set_ecology(&mut self, ecology: Option<Ecology>) {
+        self.ecology = ecology;
+        self.populations.clear();
+
+        let energy = self.initial_energy();
+        for p in self.particles.iter_mut() {
+            p.energy = energy;
+        }
+    }
+
+    // Energy new particles start with, 0 without an ecology.
+    pub fn initial_energy(&self) -> f64 {
+        self.ecology
+            .as_ref()
+            .map_or(0.0, |ecology| ecology.initial_energy as f64)
+    }
+
+    // Number of particles of every type.
+    pub fn population(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.types.size()];
+        for p in &self.particles {
+            counts[p.particle_type] += 1;
+        }
+        counts
+    }
+
+    // Populations of every type after each of the last steps with an ecology,
+    // oldest first.
+    pub fn population_history(&self) -> &VecDeque<Vec<usize>> {
+        &self.populations
+    }
+
+    pub fn set_reactions(&mut self, reactions: Vec<Reaction>) {
+        self.types.set_reactions(reactions);
+        self.reset_reaction_counts();
+    }
+
+    // Number of times each reaction of the types happened since they were set,
+    // in the same order.
+    pub fn reaction_counts(&self) -> &[u64] {
+        &self.reaction_counts
+    }
+
+    fn reset_reaction_counts(&mut self) {
+        self.reaction_counts.clear();
+        self.reaction_counts.resize(self.types.reactions().len(), 0);
+    }
+
+    pub fn bonds(&self) -> &[Bond] {
+        &self.bonds
+    }
+
+    pub fn clear_bonds(&mut self) {
+        self.bonds.clear();
+    }
+
+    // Replaces the bond parameters of all types with `rules`, leaving the
+    // other pairs unable to bond. An empty `max_bonds` lifts the limit for
+    // every type.
+    pub fn set_bond_rules(&mut self, rules: &[BondRule], max_bonds: &[usize]) {
+        for i in 0..self.types.size() {
+            for j in i..self.types.size() {
+                self.types.set_bond(i, j, 0.0, 0.0, 0.0, 0.0);
+            }
+            self.types
+                .set_max_bonds(i, max_bonds.get(i).copied().unwrap_or(0));
+        }
+
+        for rule in rules {
+            self.types.set_bond(
+                rule.a,
+                rule.b,
+                rule.radius,
+                rule.rest_length,
+                rule.stiffness,
+                rule.break_force,
+            );
+        }
+    }
+
+    // Pulls particles within `radius` of the world position (x, y) towards
+    // it, or pushes them away for a negative `strength`, until cleared.
+    pub fn set_brush(&mut self, x: f32, y: f32, radius: f32, strength: f32) {
+        self.brush = Some(ForceField::new(FieldKind::Point {
+            x: x / self.width,
+            y: y / self.height,
+            strength,
+            radius,
+        }));
+    }
+
+    pub fn clear_brush(&mut self) {
+        self.brush = None;
+    }
+
+    pub fn thermostat(&self) -> Thermostat {
+        self.thermostat
+    }
+
+    pub fn set_thermostat(&mut self, thermostat: Thermostat) {
+        self.thermostat = thermostat;
+    }
+
+    // Advances the simulation by `dt`, measured in steps of the original fixed
+    // step model, i.e. `step(1.0)` applies the forces once.
+    pub fn step(&mut self, dt: f64) {
+        self.previous_positions.clear();
+        self.previous_positions
+            .extend(self.particles.iter().map(|p| (p.x, p.y)));
+
+        let params = ForceParams {
+            types: &self.types,
+            width: self.width as f64,
+            height: self.height as f64,
+            boundary: self.boundary,
+            fields: &self.fields,
+            brush: self.brush.as_ref(),
+            bonds: &self.bonds,
+        };
+        let solver = &mut self.solver;
+
+        self.integrator.integrate(
+            &mut self.particles,
+            dt,
+            &mut self.integrator_scratch,
+            |particles, out| solver.compute(&params, particles, out),
+        );
+
+        let damping: Vec<f64> = (0..self.types.size())
+            .map(|i| (1.0 - *self.types.get_friction(i).unwrap() as f64).powf(dt))
+            .collect();
+
+        let noise: Vec<f64> = (0..self.types.size())
+            .map(|i| {
+                let temperature = *self.types.get_temperature(i).unwrap() as f64;
+                let mass = *self.types.get_mass(i).unwrap() as f64;
+                (temperature * dt / mass).sqrt()
+            })
+            .collect();
+
+        let width = self.width as f64;
+        let height = self.height as f64;
+        let mut absorbed = false;
+        let mut keep = Vec::new();
+
+        for (i, p) in self.particles.iter_mut().enumerate() {
+            p.vx *= damping[p.particle_type];
+            p.vy *= damping[p.particle_type];
+
+            // Only draw when needed, so that cold worlds keep their sequence
+            let sigma = noise[p.particle_type];
+            if sigma > 0.0 {
+                let nx: f64 = StandardNormal.sample(&mut self.rng);
+                let ny: f64 = StandardNormal.sample(&mut self.rng);
+                p.vx += sigma * nx;
+                p.vy += sigma * ny;
+            }
+
+            let radius = *self.types.get_radius(p.particle_type).unwrap() as f64;
+            for obstacle in &self.obstacles {
+                obstacle.collide(p, self.previous_positions[i], radius, width, height);
+            }
+
+            // Check for wall collision
+            let margin = 2.0 * radius;
+            let inside = self.boundary.confine(p, margin, width, height);
+
+            // Only track which particles to keep once one is absorbed
+            if !inside && !absorbed {
+                absorbed = true;
+                keep.resize(i, true);
+            }
+            if absorbed {
+                keep.push(inside);
+            }
+        }
+
+        if absorbed {
+            self.retain_particles(&keep);
+        }
+
+        self.react(dt);
+        self.live(dt);
+        self.update_bonds();
+
+        self.thermostat.apply(&mut self.particles, &self.types);
+    }
+
+    // Turns reactants near a catalyst into products. All reactions see the
+    // types from before the step, so they don't chain within a step.
+    fn react(&mut self, dt: f64) {
+        let reactions = self.types.reactions();
+        if reactions.is_empty() {
+            return;
+        }
+
+        let width = self.width as f64;
+        let height = self.height as f64;
+        let largest_radius = reactions.iter().map(|r| r.radius).fold(0.0, f32::max);
+        let (origin, grid_width, grid_height) =
+            self.boundary.grid_area(&self.particles, width, height);
+        self.contact_grid.rebuild(
+            &self.particles,
+            origin,
+            grid_width,
+            grid_height,
+            largest_radius as f64,
+        );
+
+        let mut products: Vec<Option<usize>> = vec![None; self.particles.len()];
+        for (index, reaction) in reactions.iter().enumerate() {
+            // The probability is per step of `dt = 1`
+            let probability = 1.0 - (1.0 - reaction.probability as f64).powf(dt);
+            let r2 = reaction.radius as f64 * reaction.radius as f64;
+
+            for (i, p) in self.particles.iter().enumerate() {
+                if p.particle_type != reaction.reactant || products[i].is_some() {
                     continue;
                 }
 
-                // Wtf is this?
-                let first = i.min(j);
-                let second = i.max(j);
-                let (head, tail) = self.particles.split_at_mut(second);
-
-                // Current particle
-                let p;
-                // Other particle
-                let q;
-                if i < j {
-                    p = &mut head[first];
-                    q = &mut tail[0];
-                } else {
-                    p = &mut tail[0];
-                    q = &mut head[first];
+                let mut near = false;
+                self.contact_grid
+                    .for_each_neighbor(p.x, p.y, self.boundary.wraps(), |j| {
+                        let q = &self.particles[j];
+                        if near || j == i || q.particle_type != reaction.catalyst {
+                            return;
+                        }
+
+                        let (dx, dy) =
+                            self.boundary
+                                .displacement(q.x - p.x, q.y - p.y, width, height);
+                        near = dx * dx + dy * dy <= r2;
+                    });
+
+                // Only draw when needed, so that worlds without reactions keep
+                // their sequence
+                if near && self.rng.gen::<f64>() < probability {
+                    products[i] = Some(reaction.product);
+                    self.reaction_counts[index] += 1;
                 }
+            }
+        }
 
-                // Get deltas
-                let mut dx = q.x - p.x;
-                let mut dy = q.y - p.y;
+        for (p, product) in self.particles.iter_mut().zip(products) {
+            if let Some(product) = product {
+                p.particle_type = product;
+            }
+        }
+    }
 
-                if self.wrap {
-                    if dx > self.width as f64 * 0.5 {
-                        dx -= self.width as f64;
-                    } else if dx < -self.width as f64 * 0.5 {
-                        dx += self.width as f64;
-                    }
+    // Feeds, starves, splits and kills particles according to the ecology, and
+    // records the resulting populations.
+    fn live(&mut self, dt: f64) {
+        let ecology = match &self.ecology {
+            Some(ecology) => ecology,
+            None => return,
+        };
+
+        let size = self.types.size();
+        let width = self.width as f64;
+        let height = self.height as f64;
+
+        // Energy taken per step by `eater * size + food`
+        let mut rates = vec![0.0; size * size];
+        for diet in ecology.diets.iter() {
+            if diet.eater < size && diet.food < size {
+                rates[diet.eater * size + diet.food] += diet.rate as f64;
+            }
+        }
+
+        let mut gains = vec![0.0; self.particles.len()];
+        if rates.iter().any(|&rate| rate > 0.0) {
+            let r2 = ecology.radius as f64 * ecology.radius as f64;
+            let (origin, grid_width, grid_height) =
+                self.boundary.grid_area(&self.particles, width, height);
+            self.contact_grid.rebuild(
+                &self.particles,
+                origin,
+                grid_width,
+                grid_height,
+                ecology.radius as f64,
+            );
+
+            for (i, p) in self.particles.iter().enumerate() {
+                self.contact_grid
+                    .for_each_neighbor(p.x, p.y, self.boundary.wraps(), |j| {
+                        let q = &self.particles[j];
+                        let rate = rates[p.particle_type * size + q.particle_type];
+                        if j == i || rate == 0.0 {
+                            return;
+                        }
+
+                        let (dx, dy) =
+                            self.boundary
+                                .displacement(q.x - p.x, q.y - p.y, width, height);
+                        if dx * dx + dy * dy <= r2 {
+                            gains[i] += rate * dt;
+                            gains[j] -= rate * dt;
+                        }
+                    });
+            }
+        }
+
+        let split_energy = ecology.split_energy as f64;
+        let mut population = self.particles.len();
+        let mut keep = Vec::with_capacity(population);
+        let mut children = Vec::new();
+
+        for (p, gain) in self.particles.iter_mut().zip(gains) {
+            p.energy += gain - ecology.decay(p.particle_type) as f64 * dt;
+
+            let alive = p.energy > 0.0;
+            keep.push(alive);
+            if !alive {
+                population -= 1;
+                continue;
+            }
+
+            if p.energy >= split_energy && population < ecology.max_particles {
+                p.energy *= 0.5;
+
+                // Next to the parent, in a random direction
+                let angle = self.rng.gen_range(0.0..std::f64::consts::TAU);
+                let offset = *self.types.get_radius(p.particle_type).unwrap() as f64;
+                let mut child = p.clone();
+                child.x += offset * angle.cos();
+                child.y += offset * angle.sin();
+                children.push(child);
+                population += 1;
+            }
+        }
+
+        if keep.contains(&false) {
+            self.retain_particles(&keep);
+        }
+        for child in children {
+            self.add_particle(child);
+        }
 
-                    if dy > self.height as f64 * 0.5 {
-                        dy -= self.height as f64;
-                    } else if dy < -self.height as f64 * 0.5 {
-                        dy += self.height as f64;
+        self.populations.push_back(self.population());
+        if self.populations.len() > POPULATION_HISTORY {
+            self.populations.pop_front();
+        }
+    }
+
+    // Breaks bonds that are overloaded or no longer allowed between their
+    // types, then bonds unbonded pairs within the bond radius of their types.
+    fn update_bonds(&mut self) {
+        let width = self.width as f64;
+        let height = self.height as f64;
+        let types = &self.types;
+        let particles = &self.particles;
+        let boundary = self.boundary;
+
+        self.bonds.retain(|bond| {
+            let (p, q) = (&particles[bond.a], &particles[bond.b]);
+            let (a, b) = (p.particle_type, q.particle_type);
+            if *types.get_bond_r(a, b).unwrap() == 0.0 {
+                return false;
+            }
+
+            let break_force = *types.get_bond_break(a, b).unwrap() as f64;
+            if break_force == 0.0 {
+                return true;
+            }
+
+            let (dx, dy) = boundary.displacement(q.x - p.x, q.y - p.y, width, height);
+            bond::spring_force(types, a, b, (dx * dx + dy * dy).sqrt()).abs() <= break_force
+        });
+
+        let largest_radius = types.largest_bond_r();
+        if largest_radius == 0.0 {
+            return;
+        }
+
+        let mut counts = vec![0; particles.len()];
+        let mut bonded = HashSet::with_capacity(self.bonds.len());
+        for bond in &self.bonds {
+            counts[bond.a] += 1;
+            counts[bond.b] += 1;
+            bonded.insert((bond.a, bond.b));
+        }
+
+        let (origin, grid_width, grid_height) = boundary.grid_area(particles, width, height);
+        self.contact_grid.rebuild(
+            particles,
+            origin,
+            grid_width,
+            grid_height,
+            largest_radius as f64,
+        );
+
+        let full = |index: usize, count: usize| {
+            let max_bonds = *types.get_max_bonds(particles[index].particle_type).unwrap();
+            max_bonds > 0 && count >= max_bonds
+        };
+
+        for (i, p) in particles.iter().enumerate() {
+            self.contact_grid
+                .for_each_neighbor(p.x, p.y, boundary.wraps(), |j| {
+                    if j <= i || full(i, counts[i]) || full(j, counts[j]) {
+                        return;
                     }
-                }
 
-                // Get distance squared
-                let r2 = dx * dx + dy * dy;
-                let min_r = *self
-                    .types
-                    .get_min_r(p.particle_type, q.particle_type)
-                    .unwrap();
-                let max_r = *self
-                    .types
-                    .get_max_r(p.particle_type, q.particle_type)
-                    .unwrap();
-
-                if r2 > max_r as f64 * max_r as f64 || r2 < 0.01 {
-                    continue;
-                }
+                    let q = &particles[j];
+                    let radius =
+                        *types.get_bond_r(p.particle_type, q.particle_type).unwrap() as f64;
+                    if radius == 0.0 || bonded.contains(&(i, j)) {
+                        return;
+                    }
 
-                // Normalize displacement
-                let r = r2.sqrt();
-                dx /= r;
-                dy /= r;
-
-                // Calculate force
-                let mut f = 0.0;
-                if r > min_r as f64 {
-                    if self.flat_force {
-                        f = *self
-                            .types
-                            .get_attract(p.particle_type, q.particle_type)
-                            .unwrap() as f64;
-                    } else {
-                        let numer = 2.0 * (r - 0.5 * (max_r as f64 + min_r as f64)).abs();
-                        let denom = max_r as f64 - min_r as f64;
-                        f = *self
-                            .types
-                            .get_attract(p.particle_type, q.particle_type)
-                            .unwrap() as f64
-                            * (1.0 - numer / denom);
+                    let (dx, dy) = boundary.displacement(q.x - p.x, q.y - p.y, width, height);
+                    if dx * dx + dy * dy <= radius * radius {
+                        counts[i] += 1;
+                        counts[j] += 1;
+                        bonded.insert((i, j));
+                        self.bonds.push(Bond::new(i, j));
                     }
-                } else {
-                    f = R_SMOOTH
-                        * min_r as f64
-                        * (1.0 / (min_r as f64 + R_SMOOTH) - 1.0 / (r + R_SMOOTH));
-                }
+                });
+        }
+    }
 
-                (*p).vx += f * dx;
-                (*p).vy += f * dy;
-            }
+    // Position of a particle `alpha` of the way from its position before the
+    // last step to its current one.
+    pub fn interpolated_position(&self, index: usize, alpha: f64) -> (f64, f64) {
+        let p = &self.particles[index];
+
+        let (px, py) = match self.previous_positions.get(index) {
+            Some(&previous) => previous,
+            None => return (p.x, p.y),
+        };
+
+        // Don't sweep across the world when wrapping around an edge
+        let (dx, dy) =
+            self.boundary
+                .displacement(p.x - px, p.y - py, self.width as f64, self.height as f64);
+
+        (px + dx * alpha, py + dy * alpha)
+    }
+
+    #[cfg(feature = "render")]
+    pub fn draw(&self, camera: &Camera, alpha: f64) {
+        use macroquad::prelude::{draw_circle, draw_line, Color};
+
+        let width = self.width as f64;
+        let height = self.height as f64;
+
+        for bond in &self.bonds {
+            let p = &self.particles[bond.a];
+            let color: Color = (*self.types.get_color(p.particle_type).unwrap()).into();
+            let (ax, ay) = self.interpolated_position(bond.a, alpha);
+            let (bx, by) = self.interpolated_position(bond.b, alpha);
+
+            // Across the edge of a wrapping world, like the forces
+            let (dx, dy) = self.boundary.displacement(bx - ax, by - ay, width, height);
+            let length = (dx * dx + dy * dy).sqrt() as f32 * camera.zoom;
+            let (dx, dy) = (dx as f32 * camera.zoom, dy as f32 * camera.zoom);
+
+            camera.for_each_image(ax as f32, ay as f32, length, |x, y| {
+                draw_line(x, y, x + dx, y + dy, 1.0, color);
+            });
+        }
+
+        for (i, p) in self.particles.iter().enumerate() {
+            let color: Color = (*self.types.get_color(p.particle_type).unwrap()).into();
+            let circle_radius = *self.types.get_radius(p.particle_type).unwrap() * camera.zoom;
+            let (x, y) = self.interpolated_position(i, alpha);
+
+            camera.for_each_image(x as f32, y as f32, circle_radius, |x, y| {
+                draw_circle(x, y, circle_radius, color);
+            });
         }
+    }
+}
 
-        // Update position
-        for i in 0..size {
-            // Current particle
-            let p = &mut self.particles[i];
+// Draws from the inclusive range, without using the random engine if the range
+// is a single value so that presets without one still reproduce their worlds.
+fn sample_range<R: RngCore>(rng: &mut R, lower: f32, upper: f32) -> f32 {
+    if lower < upper {
+        Uniform::new_inclusive(lower, upper).sample(rng)
+    } else {
+        lower
+    }
+}
 
-            // Update position and velocity
-            (*p).x += p.vx;
-            (*p).y += p.vy;
-            (*p).vx *= 1.0 - self.friction as f64;
-            (*p).vy *= 1.0 - self.friction as f64;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::force_law::ForceLaw;
+    use crate::preset::PRESETS;
 
-            // Check for wall collision
-            if self.wrap {
-                if p.x < 0.0 {
-                    (*p).x += self.width as f64;
-                } else if p.x >= self.width as f64 {
-                    (*p).x -= self.width as f64;
-                }
+    // Universe running `preset` from a fixed seed.
+    fn seeded(preset: &str, boundary: Boundary) -> Universe {
+        let mut universe = Universe::new(800.0, 600.0);
+        universe.set_rng_seed(42);
+        universe.load_preset(PRESETS.get(preset).unwrap());
+        universe.set_boundary(boundary);
+        universe
+    }
 
-                if p.y < 0.0 {
-                    (*p).y += self.height as f64;
-                } else if p.y >= self.height as f64 {
-                    (*p).y -= self.height as f64;
-                }
-            } else {
-                if p.x <= DIAMETER as f64 {
-                    (*p).vx = -p.vx;
-                    (*p).x = DIAMETER as f64;
-                } else if p.x >= self.width as f64 - DIAMETER as f64 {
-                    (*p).vx = -p.vx;
-                    (*p).x = self.width as f64 - DIAMETER as f64;
-                }
+    #[test]
+    fn grid_matches_brute_force() {
+        for boundary in [Boundary::Wrap, Boundary::Reflect] {
+            let mut grid = seeded("Chaos", boundary);
+            let mut brute_force = seeded("Chaos", boundary);
+            grid.set_neighbor_search(NeighborSearch::Grid);
+            brute_force.set_neighbor_search(NeighborSearch::BruteForce);
 
-                if p.y <= DIAMETER as f64 {
-                    (*p).vy = -p.vy;
-                    (*p).y = DIAMETER as f64;
-                } else if p.y >= self.height as f64 - DIAMETER as f64 {
-                    (*p).vy = -p.vy;
-                    (*p).y = self.height as f64 - DIAMETER as f64;
-                }
+            for _ in 0..20 {
+                grid.step(1.0);
+                brute_force.step(1.0);
+            }
+
+            for (p, q) in grid.particles().iter().zip(brute_force.particles()) {
+                assert!(
+                    (p.x - q.x).abs() < 1e-6 && (p.y - q.y).abs() < 1e-6,
+                    "{:?}: ({}, {}) != ({}, {})",
+                    boundary,
+                    p.x,
+                    p.y,
+                    q.x,
+                    q.y
+                );
             }
         }
     }
 
-    pub fn draw(&mut self, opacity: f32) {
-        let circle_radius = RADIUS * self.zoom;
-        for p in self.particles.iter() {
-            let x = (p.x as f32 - self.center_x) * self.zoom + self.width / 2.0;
-            let y = (p.y as f32 - self.center_y) * self.zoom + self.height / 2.0;
+    #[test]
+    fn snapshots_resume_identically() {
+        let dir = std::env::temp_dir().join(format!("particle-life-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for file in ["snapshot.bin", "snapshot.json"] {
+            let mut universe = seeded("Chaos", Boundary::Reflect);
+            universe.set_temperature(0.01);
+            for _ in 0..5 {
+                universe.step(1.0);
+            }
 
-            let color = self.types.get_color_mut(p.particle_type).unwrap();
-            color.a = opacity;
-            draw_circle(x, y, circle_radius, *color);
+            let path = dir.join(file);
+            universe.save_snapshot(&path).unwrap();
+            let mut restored = Universe::new(1.0, 1.0);
+            restored.load_snapshot(&path).unwrap();
+
+            for _ in 0..20 {
+                universe.step(1.0);
+                restored.step(1.0);
+            }
+
+            for (p, q) in universe.particles().iter().zip(restored.particles()) {
+                assert_eq!(
+                    [p.x, p.y, p.vx, p.vy].map(f64::to_bits),
+                    [q.x, q.y, q.vx, q.vy].map(f64::to_bits),
+                    "{}",
+                    file
+                );
+            }
+            assert_eq!(universe.particles().len(), restored.particles().len());
         }
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
-    // pub fn get_index(&self, x: f64, y: f64) -> Option<usize> {
-    //     let [cx, cy] = self.to_center(x, y);
+    #[test]
+    fn reciprocal_forces_conserve_momentum() {
+        for neighbor_search in [NeighborSearch::Grid, NeighborSearch::BruteForce] {
+            let mut universe = seeded("Frictionless", Boundary::Wrap);
+            universe.set_force_mode(ForceMode::Reciprocal);
+            universe.set_neighbor_search(neighbor_search);
+            let (px, py) = universe.momentum();
 
-    //     for (index, p) in self.particles.iter().enumerate() {
-    //         let dx = p.x - cx;
-    //         let dy = p.y - cx;
+            for _ in 0..50 {
+                universe.step(1.0);
 
-    //         if dx * dx + dy * dy < RADIUS as f64 * RADIUS as f64 {
-    //             return Some(index);
-    //         }
-    //     }
+                let (qx, qy) = universe.momentum();
+                assert!(
+                    (qx - px).abs() < 1e-9 && (qy - py).abs() < 1e-9,
+                    "{:?}: ({}, {}) != ({}, {})",
+                    neighbor_search,
+                    qx,
+                    qy,
+                    px,
+                    py
+                );
+            }
+        }
+    }
 
-    //     None
-    // }
+    // Negative integral of the kernel of types `a` and `b` from `r` out to
+    // `max_r`, where the force ends.
+    fn pair_potential(types: &ParticleTypes, a: usize, b: usize, r: f64) -> f64 {
+        let min_r = *types.get_min_r(a, b).unwrap() as f64;
+        let max_r = *types.get_max_r(a, b).unwrap() as f64;
+        let attract = *types.get_attract(a, b).unwrap() as f64;
+        let kernel = types.get_kernel(a, b).unwrap();
+        let force = |r: f64| kernel.force(r, min_r, max_r, attract);
 
-    // pub fn get_particle_x(&self, index: usize) -> Option<f32> {
-    //     self.particles.get(index).map(|p| p.x)
-    // }
+        // Simpson's rule between the kinks of the kernel
+        let mut potential = 0.0;
+        for (lower, upper) in [
+            (0.0, min_r),
+            (min_r, 0.5 * (min_r + max_r)),
+            (0.5 * (min_r + max_r), max_r),
+        ] {
+            let (lower, upper) = (lower.max(r), upper.max(r));
+            let n = 16;
+            let h = (upper - lower) / n as f64;
+            let sum: f64 = (0..=n)
+                .map(|k| {
+                    let weight = if k == 0 || k == n {
+                        1.0
+                    } else if k % 2 == 1 {
+                        4.0
+                    } else {
+                        2.0
+                    };
+                    weight * force(lower + k as f64 * h)
+                })
+                .sum();
+            potential -= sum * h / 3.0;
+        }
+        potential
+    }
 
-    // pub fn get_particle_y(&self, index: usize) -> Option<f32> {
-    //     self.particles.get(index).map(|p| p.y)
-    // }
+    // Kinetic plus potential energy of the reciprocal forces.
+    fn total_energy(universe: &Universe) -> f64 {
+        let particles = universe.particles();
+        let types = universe.types();
+        let width = universe.width() as f64;
+        let height = universe.height() as f64;
+        let mut potential = 0.0;
 
-    // pub fn to_center(&self, x: f32, y: f32) -> [f32; 2] {
-    //     let cx = self.center_x + (x - self.width / 2.0) / self.zoom;
-    //     let cy = self.center_y + (y - self.height / 2.0) / self.zoom;
-    //     [cx, cy]
-    // }
+        for (i, p) in particles.iter().enumerate() {
+            for q in &particles[i + 1..] {
+                let (dx, dy) =
+                    universe
+                        .boundary()
+                        .displacement(q.x - p.x, q.y - p.y, width, height);
+                let r = (dx * dx + dy * dy).sqrt();
+                let (a, b) = (p.particle_type, q.particle_type);
+                if r < *types.get_max_r(a, b).unwrap() as f64 {
+                    potential +=
+                        0.5 * (pair_potential(types, a, b, r) + pair_potential(types, b, a, r));
+                }
+            }
+        }
 
-    pub fn set_zoom(&mut self, cx: f32, cy: f32, zoom: f32) {
-        // Apply the zoom
-        self.center_x = cx;
-        self.center_y = cy;
-        self.zoom = zoom.max(1.0);
+        universe.kinetic_energy() + potential
+    }
 
-        // Clamp to make sure camera doesn't go out of bounds
-        self.center_x = self
-            .center_x
-            .min(self.width * (1.0 - 0.5 / self.zoom))
-            .max(self.width * (0.5 / self.zoom));
-        self.center_y = self
-            .center_y
-            .min(self.height * (1.0 - 0.5 / self.zoom))
-            .max(self.height * (0.5 / self.zoom));
+    // Largest change of the total energy over `steps` steps of `dt`.
+    fn energy_drift(seed: u64, integrator: Integrator, dt: f64, steps: usize) -> f64 {
+        let mut universe = Universe::new(800.0, 600.0);
+        universe.set_rng_seed(seed);
+        universe.load_preset(PRESETS.get("Frictionless").unwrap());
+        universe.set_boundary(Boundary::Wrap);
+        universe.set_kernel(Kernel::Smoothstep);
+        universe.set_force_mode(ForceMode::Reciprocal);
+        universe.set_integrator(integrator);
+
+        let initial = total_energy(&universe);
+        let mut drift: f64 = 0.0;
+        for _ in 0..steps {
+            universe.step(dt);
+            drift = drift.max((total_energy(&universe) - initial).abs());
+        }
+        drift
+    }
+
+    #[test]
+    fn verlet_drifts_less_than_euler() {
+        for seed in 0..4 {
+            let euler = energy_drift(seed, Integrator::SymplecticEuler, 0.5, 100);
+            let verlet = energy_drift(seed, Integrator::VelocityVerlet, 0.5, 100);
+            assert!(
+                verlet < euler,
+                "seed {}: Verlet drifted {}, Euler {}",
+                seed,
+                verlet,
+                euler
+            );
+        }
     }
 }